@@ -0,0 +1,27 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use mm_engine::ipc::*;
+
+// treats the input as whatever a bot left in shared memory. reading it back must only ever
+// succeed or return a ResponseError, never panic or read an invalid value
+fuzz_target!(|data: &[u8]| {
+    let mut region = vec![0u64; SHM_SIZE.div_ceil(8)];
+    let shm = unsafe { std::slice::from_raw_parts_mut(region.as_mut_ptr() as *mut u8, SHM_SIZE) };
+    let len = data.len().min(SHM_SIZE);
+    shm[..len].copy_from_slice(&data[..len]);
+    // a wrong checksum is rejected up front, so seal the region to reach the validation below
+    seal_response(shm);
+
+    let _ = read_response::<HandshakeProtocol>(shm);
+    let _ = read_response::<ResetProtocol>(shm);
+    if let Ok(actions) = read_response::<TickProtocol>(shm) {
+        // touch every enum so an invalid tag would be observed
+        for action in &actions {
+            let _ = action.pass.option();
+            let _ = action.tackle.option();
+            let _ = action.shoot.option();
+            let _ = action.sprint as u8;
+        }
+    }
+});
@@ -1,23 +1,148 @@
-use std::path::PathBuf;
+use std::path::{ Path, PathBuf };
 use std::fs::File;
+use std::net::{ IpAddr, Ipv4Addr };
 use std::io::{ self, BufWriter, Write };
-use tokio::sync::mpsc;
-use clap::Parser;
+use anyhow::Context;
+use tokio::{ io::AsyncWriteExt, net::UnixStream, sync::mpsc };
+use clap::{ Parser, Subcommand };
+use crate::game::{ config::GameConfig, state::{ Team, TeamPair }, stats::{ TeamInfo, TieBreak } };
+use crate::replay::ReplayWriter;
 
 
 #[derive(Parser, Clone, Debug)]
-#[command(version, about, long_about = None)]
+#[command(version, about, long_about = None, subcommand_negates_reqs = true, args_conflicts_with_subcommands = true)]
 pub struct ArgConfig {
+    #[command(subcommand)]
+    pub command: Option<EngineCommand>,
+    /// print the ipc protocol table and exit
+    #[arg(long = "list-protocols")]
+    pub list_protocols: bool,
     /// path to bot a binary
-    pub bot_a: PathBuf,
+    #[arg(required_unless_present_any = ["replay", "list_protocols"])]
+    pub bot_a: Option<PathBuf>,
     /// path to bot b binary
-    pub bot_b: PathBuf,
-    /// output sources to print (e.g., -p a,b,g)
-    #[arg(short = 'p', long = "print", value_delimiter = ',', value_parser = parse_source)]
-    pub print: Option<Vec<OutputSource>>,
-    /// output sources redirected to file, format: a:foo.txt g:log.json
+    #[arg(required_unless_present_any = ["replay", "list_protocols"])]
+    pub bot_b: Option<PathBuf>,
+    /// bot a's name in logs, results and snapshots, the binary's file name when omitted. its
+    /// output lines are tagged with the side and name, e.g. #[A:name]
+    #[arg(long = "name-a")]
+    pub name_a: Option<String>,
+    /// bot b's name in logs, results and snapshots, the binary's file name when omitted. its
+    /// output lines are tagged with the side and name, e.g. #[B:name]
+    #[arg(long = "name-b")]
+    pub name_b: Option<String>,
+    /// bot a's color in results and snapshots, a color name or #rgb/#rrggbb
+    #[arg(long = "color-a", value_parser = parse_color, default_value = "#d33")]
+    pub color_a: String,
+    /// bot b's color in results and snapshots, a color name or #rgb/#rrggbb
+    #[arg(long = "color-b", value_parser = parse_color, default_value = "#33d")]
+    pub color_b: String,
+    /// json file with the game config, the built-in defaults are used when omitted. repeat it to
+    /// vary the config across a --games series, game i plays the (i mod count)th config
+    #[arg(short = 'c', long = "config")]
+    pub config: Vec<PathBuf>,
+    /// end the match at max_ticks even when tied, skipping the endgame extension
+    #[arg(long = "no-overtime")]
+    pub no_overtime: bool,
+    /// output sources to print (e.g., -p a,b,g or -p abg)
+    #[arg(short = 'p', long = "print", value_parser = parse_sources)]
+    pub print: Option<Vec<OutputSources>>,
+    /// output sources redirected to file, format: a:foo.txt bg:log.json. aerr and berr split a
+    /// bot's stderr from its stdout (e.g., -o aerr:bot_a.err), otherwise it goes wherever a or b does
     #[arg(short = 'o', long = "output", value_parser = parse_output_mappings)]
     pub output: Option<Vec<OutputMapping>>,
+    /// unix domain socket to stream the gamelog to
+    #[arg(long = "socket")]
+    pub socket: Option<PathBuf>,
+    /// render the final frame as an svg to this path
+    #[arg(long = "snapshot")]
+    pub snapshot: Option<PathBuf>,
+    /// number of trailing frames drawn as motion trails in the snapshot
+    #[arg(long = "snapshot-trail", default_value_t = 0)]
+    pub snapshot_trail: usize,
+    /// write every possession transition of the match as a json array to this path
+    #[arg(long = "possession-log")]
+    pub possession_log: Option<PathBuf>,
+    /// write the config, result, tick count, elapsed time and stats of the match as one json
+    /// file to this path
+    #[arg(long = "summary")]
+    pub summary: Option<PathBuf>,
+    /// record the gamelog to this path so the match can be replayed later. the file holds the
+    /// gamelog lines as text (config, json states, events and the result), so it is about as
+    /// large as the gamelog itself
+    #[arg(long = "record")]
+    pub record: Option<PathBuf>,
+    /// write every tick's state to its own numbered json file in this directory, next to a
+    /// config.json
+    #[arg(long = "frames-dir")]
+    pub frames_dir: Option<PathBuf>,
+    /// re-emit a recorded match instead of running bots
+    #[arg(long = "replay")]
+    pub replay: Option<PathBuf>,
+    /// round the floats in logged states to this many decimal places. the simulation itself keeps
+    /// full precision (0-9)
+    #[arg(long = "decimals", value_parser = clap::value_parser!(u32).range(0..=9))]
+    pub decimals: Option<u32>,
+    /// wrap every gamelog line in a {"type", "data"} json envelope
+    #[arg(long = "tagged")]
+    pub tagged: bool,
+    /// tie breaks applied in order when goals are equal (e.g., -t possession,shots,coin)
+    #[arg(short = 't', long = "tie-break", value_delimiter = ',', value_parser = parse_tie_break)]
+    pub tie_break: Vec<TieBreak>,
+    /// warn when a bot returns all-default actions for this many consecutive ticks (0 to disable)
+    #[arg(long = "idle-warn-ticks", default_value_t = 300)]
+    pub idle_warn_ticks: u32,
+    /// print a one-line summary of this team's inputs every tick (e.g., t=42 p0:0.8 p1:pass), a
+    /// lighter alternative to reading the full actions
+    #[arg(long = "debug-inputs", value_parser = parse_team)]
+    pub debug_inputs: Option<Team>,
+    /// rng seed, picked at random and logged when omitted
+    #[arg(long = "seed")]
+    pub seed: Option<u64>,
+    /// score recorded when a bot exits mid match and forfeits, format: winner:loser. the score
+    /// at the time of the forfeit stands when omitted
+    #[arg(long = "forfeit-score", value_parser = parse_score)]
+    pub forfeit_score: Option<TeamPair<u32>>,
+    /// play this many matches in a row, swapping the bots' sides every other match, and print
+    /// the combined results. each match writes its snapshot, summary and possession log to the
+    /// given path suffixed with its game number (e.g. summary-2.json)
+    #[arg(long = "games", default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    pub games: u32,
+    /// initial score, format: a:b
+    #[arg(long = "start-score", value_parser = parse_score, default_value = "0:0")]
+    pub start_score: TeamPair<u32>,
+    /// most compute time a bot can bank, in engine ticks
+    #[arg(long = "compute-budget", default_value_t = crate::engine::TOTAL_COMPUTE_TICKS)]
+    pub compute_budget: u32,
+    /// engine ticks a bot can spend on each response without drawing from its budget
+    #[arg(long = "delay-ticks", default_value_t = crate::engine::DELAY_TICKS)]
+    pub delay_ticks: u32,
+    /// measure bot budgets in a fixed notional engine tick of this many microseconds instead of the
+    /// measured physics time, so timeouts don't depend on how fast the host runs the physics
+    #[arg(long = "tick-time-us", value_parser = clap::value_parser!(u64).range(1..))]
+    pub tick_time_us: Option<u64>,
+    /// skip compute budget accounting so bots are never charged for their response time, for
+    /// ci and benchmarks rather than competition play. each response still times out once it
+    /// takes the whole budget
+    #[arg(long = "fast")]
+    pub fast: bool,
+    /// seconds a bot has to answer the handshake
+    #[arg(long = "handshake-timeout-secs", default_value_t = crate::engine::HANDSHAKE_TIMEOUT_SECS)]
+    pub handshake_timeout_secs: u64,
+    /// maximum bytes of stdout/stderr forwarded per bot before output is suppressed (0 for unlimited)
+    #[arg(long = "bot-output-limit", default_value_t = 16 * 1024 * 1024)]
+    pub bot_output_limit: usize,
+    /// how the engine talks to the bots: shm (a shared memory file) or tcp (a socket, for bots that
+    /// can't share a filesystem with the engine)
+    #[arg(long = "transport", value_parser = parse_transport, default_value = "shm")]
+    pub transport: TransportKind,
+    /// address the tcp transport listens on for bots. each bot proves itself with the random token
+    /// in its endpoint, so other connections to the port are dropped
+    #[arg(long = "tcp-host", default_value_t = IpAddr::V4(Ipv4Addr::LOCALHOST))]
+    pub tcp_host: IpAddr,
+    /// stretch the wall-clock time of every tick by this factor, for watching play live (e.g., 4 for 4x slower)
+    #[arg(long = "slowmo", value_parser = parse_slowmo, default_value_t = 1.0)]
+    pub slowmo: f32,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -26,6 +151,39 @@ pub enum OutputSource {
     BotA,
     BotB,
     Gamelog,
+    BotAErr,
+    BotBErr,
+}
+
+const NUM_OUTPUT_SOURCES: usize = 5;
+
+impl OutputSource {
+    /// the source a bot's stderr is sent as
+    pub fn stderr(self) -> Self {
+        match self {
+            OutputSource::BotA => OutputSource::BotAErr,
+            OutputSource::BotB => OutputSource::BotBErr,
+            source => source,
+        }
+    }
+
+    /// the source whose routing a stderr source shares when it isn't routed on its own
+    fn stdout(self) -> Self {
+        match self {
+            OutputSource::BotAErr => OutputSource::BotA,
+            OutputSource::BotBErr => OutputSource::BotB,
+            source => source,
+        }
+    }
+}
+
+/// an alias rather than a Vec so clap hands each whole list to `parse_sources` at once
+pub type OutputSources = Vec<OutputSource>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransportKind {
+    Shm,
+    Tcp,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -34,23 +192,113 @@ pub struct OutputMapping {
     pub path: PathBuf,
 }
 
+/// what a gamelog line carries. config, state, referee, result and fatal lines hold json
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineKind {
+    Config,
+    State,
+    Event,
+    /// a structured `RefereeEvent`
+    Referee,
+    Result,
+    Fatal,
+}
+
+impl LineKind {
+    pub fn tag(&self) -> &'static str {
+        match self {
+            LineKind::Config => "config",
+            LineKind::State => "state",
+            LineKind::Event => "event",
+            LineKind::Referee => "referee",
+            LineKind::Result => "result",
+            LineKind::Fatal => "fatal",
+        }
+    }
+
+    pub fn from_tag(tag: &str) -> Option<Self> {
+        match tag {
+            "config" => Some(LineKind::Config),
+            "state" => Some(LineKind::State),
+            "event" => Some(LineKind::Event),
+            "referee" => Some(LineKind::Referee),
+            "result" => Some(LineKind::Result),
+            "fatal" => Some(LineKind::Fatal),
+            _ => None,
+        }
+    }
+}
+
 pub struct Message {
     pub msg: String,
-    pub source: OutputSource
+    pub source: OutputSource,
+    pub kind: LineKind,
+}
+
+impl ArgConfig {
+    pub fn teams(&self) -> TeamPair<TeamInfo> {
+        let info = |name: &Option<String>, path: &Option<PathBuf>, color: &String, fallback: &str| TeamInfo {
+            name: name
+                .clone()
+                .or_else(|| Some(path.as_ref()?.file_name()?.to_string_lossy().into_owned()))
+                .unwrap_or_else(|| fallback.to_string()),
+            color: color.clone(),
+        };
+        TeamPair::new(
+            info(&self.name_a, &self.bot_a, &self.color_a, "A"),
+            info(&self.name_b, &self.bot_b, &self.color_b, "B"),
+        )
+    }
+}
+
+// tools that run instead of a match. a bot binary sharing a subcommand's name has to be given
+// as a path, e.g. ./config-diff
+#[derive(Subcommand, Clone, Debug)]
+pub enum EngineCommand {
+    /// print the fields b changes relative to a, or a relative to the built-in defaults when b
+    /// is omitted
+    ConfigDiff {
+        a: PathBuf,
+        b: Option<PathBuf>,
+    },
 }
 
 pub fn parse_cli() -> ArgConfig { 
     let mut cli = ArgConfig::parse();
-    if let (None, None) = (cli.print.as_ref(), cli.output.as_ref()) {
-        cli.print = Some(vec![
+    if cli.list_protocols {
+        print!("{}", crate::ipc::protocol_table());
+        std::process::exit(0);
+    }
+    if let Some(EngineCommand::ConfigDiff { a, b }) = &cli.command {
+        std::process::exit(config_diff(a, b.as_deref()));
+    }
+    if let (None, None, None) = (cli.print.as_ref(), cli.output.as_ref(), cli.socket.as_ref()) {
+        cli.print = Some(vec![vec![
             OutputSource::BotA,
             OutputSource::BotB,
             OutputSource::Gamelog,
-        ]);
+        ]]);
     }
     cli
 }
 
+/// `mm-engine config-diff <a.json> [b.json]` prints the fields b changes relative to a, or a
+/// relative to the built-in defaults when b is omitted. returns the exit code
+fn config_diff(a: &Path, b: Option<&Path>) -> i32 {
+    let load = |path: &Path| GameConfig::load(path).map_err(|e| eprintln!("{:?}", e));
+    let (old, new) = match b {
+        None => (Ok(GameConfig::default()), load(a)),
+        Some(b) => (load(a), load(b)),
+    };
+    let (Ok(old), Ok(new)) = (old, new) else {
+        return 1;
+    };
+    for diff in old.diff(&new) {
+        println!("{}", diff);
+    }
+    0
+}
+
 fn parse_output_mappings(s: &str) -> Result<OutputMapping, String> {
     let parts: Vec<&str> = s.splitn(2, ':').collect();
     if parts.len() != 2 {
@@ -59,32 +307,155 @@ fn parse_output_mappings(s: &str) -> Result<OutputMapping, String> {
             s
         ));
     }
-    let sources: Vec<OutputSource> = parts[0]
-        .split(',')
-        .map(parse_source)
-        .collect::<Result<Vec<_>, _>>()?;
+    let sources = parse_sources(parts[0])?;
     let path = PathBuf::from(parts[1]);
     Ok(OutputMapping { sources, path })
 }
 
+// either comma separated (a,b,g) or packed one char per source (abg). aerr and berr only go
+// in the comma separated form or on their own
+fn parse_sources(s: &str) -> Result<OutputSources, String> {
+    if s.contains(',') {
+        return s.split(',').map(parse_source).collect();
+    }
+    if s.is_empty() {
+        return Err("No output sources given".to_string());
+    }
+    if let Ok(source) = parse_source(s) {
+        return Ok(vec![source]);
+    }
+    s.chars().map(|c| parse_source(c.encode_utf8(&mut [0; 4]))).collect()
+}
+
 fn parse_source(s: &str) -> Result<OutputSource, String> {
      match s {
         "a" | "A" => Ok(OutputSource::BotA),
         "b" | "B" => Ok(OutputSource::BotB),
         "g" | "G" => Ok(OutputSource::Gamelog),
+        "aerr" | "AERR" => Ok(OutputSource::BotAErr),
+        "berr" | "BERR" => Ok(OutputSource::BotBErr),
         _ => Err(format!("Invalid source '{}'", s)),
     }
 }
 
+fn parse_team(s: &str) -> Result<Team, String> {
+    match s {
+        "a" | "A" => Ok(Team::A),
+        "b" | "B" => Ok(Team::B),
+        _ => Err(format!("Invalid team '{}'. Use a or b", s)),
+    }
+}
+
+fn parse_score(s: &str) -> Result<TeamPair<u32>, String> {
+    let (a, b) = s.split_once(':').ok_or_else(|| {
+        format!("Invalid format for score '{}'. Use --start-score 2:2", s)
+    })?;
+    let parse = |n: &str| n.parse::<u32>().map_err(|e| format!("Invalid score '{}': {}", n, e));
+    Ok(TeamPair::new(parse(a)?, parse(b)?))
+}
+
+fn parse_slowmo(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(factor) if factor >= 1.0 && factor.is_finite() => Ok(factor),
+        Ok(_) => Err(format!("Invalid slowmo factor '{}'. Must be at least 1", s)),
+        Err(e) => Err(format!("Invalid slowmo factor '{}': {}", s, e)),
+    }
+}
+
+fn parse_color(s: &str) -> Result<String, String> {
+    let valid = match s.strip_prefix('#') {
+        Some(hex) => matches!(hex.len(), 3 | 6) && hex.chars().all(|c| c.is_ascii_hexdigit()),
+        None => !s.is_empty() && s.chars().all(|c| c.is_ascii_alphabetic()),
+    };
+    if !valid {
+        return Err(format!("Invalid color '{}'. Use a color name, #rgb or #rrggbb", s));
+    }
+    Ok(s.to_string())
+}
+
+fn parse_transport(s: &str) -> Result<TransportKind, String> {
+    match s {
+        "shm" => Ok(TransportKind::Shm),
+        "tcp" => Ok(TransportKind::Tcp),
+        _ => Err(format!("Invalid transport '{}'. Use shm or tcp", s)),
+    }
+}
+
+fn parse_tie_break(s: &str) -> Result<TieBreak, String> {
+    match s {
+        "goals" => Ok(TieBreak::Goals),
+        "possession" => Ok(TieBreak::Possession),
+        "shots" => Ok(TieBreak::Shots),
+        "coin" => Ok(TieBreak::Coin),
+        _ => Err(format!("Invalid tie break '{}'", s)),
+    }
+}
+
+/// writes the gamelog's config and states as separate files, one per frame
+struct FrameWriter {
+    dir: PathBuf,
+    next: u32,
+}
+
+impl FrameWriter {
+    fn create(dir: &std::path::Path) -> io::Result<Self> {
+        std::fs::create_dir_all(dir)?;
+        Ok(Self { dir: dir.to_path_buf(), next: 0 })
+    }
+
+    fn write(&mut self, msg: &Message) -> io::Result<()> {
+        match (msg.source, msg.kind) {
+            (OutputSource::Gamelog, LineKind::Config) => {
+                // a new match starts numbering from zero again
+                self.next = 0;
+                std::fs::write(self.dir.join("config.json"), &msg.msg)
+            }
+            (OutputSource::Gamelog, LineKind::State) => {
+                std::fs::write(self.dir.join(format!("{:05}.json", self.next)), &msg.msg)?;
+                self.next += 1;
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
 struct OutputConfig {
     files: Box<[BufWriter<File>]>,
 
-    print: [bool; 3],
-    output_files: [Box<[u8]>; 3],
+    print: [bool; NUM_OUTPUT_SOURCES],
+    output_files: [Box<[u8]>; NUM_OUTPUT_SOURCES],
+    socket: Option<tokio::io::BufWriter<UnixStream>>,
+    record: Option<ReplayWriter>,
+    frames: Option<FrameWriter>,
+    tagged: bool,
 }
 
 impl OutputConfig {
-    fn send(&mut self, msg: Message) -> io::Result<()> {
+    fn format(&self, msg: Message) -> Message {
+        if msg.source != OutputSource::Gamelog {
+            return msg;
+        }
+        let text = match (self.tagged, msg.kind) {
+            (false, LineKind::Result) => format!("# result: {}", msg.msg),
+            (false, _) => return msg,
+            (true, LineKind::Event) => format!(
+                r#"{{"type":"event","data":{}}}"#,
+                serde_json::Value::from(msg.msg.trim_start_matches(['#', ' ']))
+            ),
+            (true, kind) => format!(r#"{{"type":"{}","data":{}}}"#, kind.tag(), msg.msg),
+        };
+        Message { msg: text, ..msg }
+    }
+
+    async fn send(&mut self, msg: Message) -> io::Result<()> {
+        if let Some(record) = &mut self.record {
+            record.write(&msg)?;
+        }
+        if let Some(frames) = &mut self.frames {
+            frames.write(&msg)?;
+        }
+        let msg = self.format(msg);
         let idx = msg.source as usize;
         if self.print[idx] {
             println!("{}", msg.msg);
@@ -92,6 +463,25 @@ impl OutputConfig {
         for i in &self.output_files[idx] {
             writeln!(self.files[*i as usize], "{}", msg.msg)?;
         }
+        if let (OutputSource::Gamelog, Some(socket)) = (msg.source, &mut self.socket) {
+            // a disconnected consumer shouldn't take the match down with it
+            let line = format!("{}\n", msg.msg);
+            if let Err(e) = async { socket.write_all(line.as_bytes()).await?; socket.flush().await }.await {
+                eprintln!("### gamelog socket disconnected: {e}");
+                self.socket = None;
+            }
+        }
+        Ok(())
+    }
+
+    /// writes out everything still buffered, called once the last message is in
+    fn flush(&mut self) -> io::Result<()> {
+        for file in &mut self.files {
+            file.flush()?;
+        }
+        if let Some(record) = &mut self.record {
+            record.flush()?;
+        }
         Ok(())
     }
 }
@@ -101,28 +491,32 @@ pub use crate::send;
 #[macro_export]
 macro_rules! send {
     ($tx:expr, $source:expr, $fmt:literal $(, $($args:expr),*)?) => {
+        $crate::send!($tx, $source, $crate::args::LineKind::Event; $fmt $(, $($args),*)?)
+    };
+    ($tx:expr, $source:expr, $kind:expr; $fmt:literal $(, $($args:expr),*)?) => {
         {
             let _ = $tx.send(Message {
                 msg: format!($fmt $(, $($args),*)?),
-                source: $source
+                source: $source,
+                kind: $kind,
             });
         }
     };
 }
 
-pub fn spawn_reciever(cli: &ArgConfig) -> io::Result<(mpsc::UnboundedSender<Message>, tokio::task::JoinHandle<io::Result<()>>)> {
+pub async fn spawn_reciever(cli: &ArgConfig) -> anyhow::Result<(mpsc::UnboundedSender<Message>, tokio::task::JoinHandle<io::Result<()>>)> {
     let (tx, mut rx) = mpsc::unbounded_channel();
 
-    let mut print = [false; 3];
+    let mut print = [false; NUM_OUTPUT_SOURCES];
 
     if let Some(prints) = &cli.print {
-        for p in prints {
+        for p in prints.iter().flatten() {
             print[*p as usize] = true;
         }
     }
 
     let mut files: Vec<BufWriter<File>> = vec![];
-    let mut output_files: [Vec<u8>; 3] = core::array::from_fn(|_| vec![]);
+    let mut output_files: [Vec<u8>; NUM_OUTPUT_SOURCES] = core::array::from_fn(|_| vec![]);
 
     if let Some(output) = &cli.output {
         for (i, o) in output.iter().enumerate() {
@@ -139,18 +533,92 @@ pub fn spawn_reciever(cli: &ArgConfig) -> io::Result<(mpsc::UnboundedSender<Mess
         }
     }
 
+    for err in [OutputSource::BotAErr, OutputSource::BotBErr] {
+        let (err, out) = (err as usize, err.stdout() as usize);
+        if !print[err] && output_files[err].is_empty() {
+            print[err] = print[out];
+            output_files[err] = output_files[out].clone();
+        }
+    }
+
+    let socket = match &cli.socket {
+        Some(path) => Some(tokio::io::BufWriter::new(
+            UnixStream::connect(path)
+                .await
+                .with_context(|| format!("failed to connect to gamelog socket {}", path.display()))?,
+        )),
+        None => None,
+    };
+
     let mut conf = OutputConfig {
         files: files.into(),
         print,
-        output_files: output_files.map(Vec::into_boxed_slice)
+        output_files: output_files.map(Vec::into_boxed_slice),
+        socket,
+        record: cli.record.as_deref().map(ReplayWriter::create).transpose()?,
+        frames: cli.frames_dir.as_deref().map(FrameWriter::create).transpose()?,
+        tagged: cli.tagged,
     };
 
     let task = tokio::task::spawn(async move {
         while let Some(msg) = rx.recv().await {
-            conf.send(msg)?;
+            conf.send(msg).await?;
         }
-        Ok(())
+        conf.flush()
     });
 
     Ok((tx, task))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn list_protocols_needs_no_bots() {
+        let cli = ArgConfig::try_parse_from(["mm-engine", "--list-protocols"]).unwrap();
+        assert!(cli.list_protocols);
+    }
+
+    #[test]
+    fn config_diff_subcommand() {
+        let cli = ArgConfig::try_parse_from(["mm-engine", "config-diff", "a.json"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(EngineCommand::ConfigDiff { a, b: None }) if a == Path::new("a.json")
+        ));
+        assert!(ArgConfig::try_parse_from(["mm-engine", "config-diff"]).is_err());
+    }
+
+    #[test]
+    fn bot_named_like_a_subcommand() {
+        let cli = ArgConfig::try_parse_from(["mm-engine", "./config-diff", "./config-diff"]).unwrap();
+        assert!(cli.command.is_none());
+        assert_eq!(cli.bot_a.as_deref(), Some(Path::new("./config-diff")));
+        assert_eq!(cli.bot_b.as_deref(), Some(Path::new("./config-diff")));
+    }
+
+    #[test]
+    fn match_needs_both_bots() {
+        assert!(ArgConfig::try_parse_from(["mm-engine", "bot"]).is_err());
+    }
+
+    #[test]
+    fn decimals_are_bounded() {
+        let parse = |decimals| ArgConfig::try_parse_from(["mm-engine", "a", "b", "--decimals", decimals]);
+        assert_eq!(parse("9").unwrap().decimals, Some(9));
+        assert!(parse("10").is_err());
+    }
+
+    #[test]
+    fn sources_parse_comma_separated_or_packed() {
+        use OutputSource::*;
+        assert_eq!(parse_sources("a,b,g"), Ok(vec![BotA, BotB, Gamelog]));
+        assert_eq!(parse_sources("abg"), Ok(vec![BotA, BotB, Gamelog]));
+        assert_eq!(parse_sources("aerr,g"), Ok(vec![BotAErr, Gamelog]));
+        assert_eq!(parse_sources("berr"), Ok(vec![BotBErr]));
+        assert_eq!(parse_sources("abx"), Err("Invalid source 'x'".to_string()));
+        assert_eq!(parse_sources("a,x"), Err("Invalid source 'x'".to_string()));
+        assert!(parse_sources("").is_err());
+    }
+}
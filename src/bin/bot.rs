@@ -0,0 +1,66 @@
+//! A minimal example bot: every player chases the ball and the carrier shoots at the opposing
+//! goal. The engine mirrors the state so a bot always attacks toward +x with players 0..NUM_PLAYERS.
+
+use mm_engine::{
+    game::{
+        config::{ GameConfig, NUM_PLAYERS },
+        state::{ BallPossessionState, PlayerAction, StateOption },
+        util::Vec2,
+    },
+    ipc::*,
+};
+use std::{ cell::RefCell, rc::Rc };
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> anyhow::Result<()> {
+    let endpoint = std::env::args().nth(1).expect("usage: bot <endpoint>");
+    let mut channel = EngineConnection::connect(&endpoint).await?;
+
+    let config: Rc<RefCell<Option<GameConfig>>> = Rc::default();
+    let strategy = Strategy {
+        on_handshake: Box::new({
+            let config = config.clone();
+            move |msg| {
+                *config.borrow_mut() = Some(msg.config.clone());
+                HandshakeResponse::CURRENT
+            }
+        }),
+        on_reset: Box::new({
+            let config = config.clone();
+            move |_msg| {
+                let config = config.borrow();
+                let center = config.as_ref().expect("reset before handshake").field.center();
+                std::array::from_fn(|i| {
+                    let spread = (i as f32 + 1.0) / (NUM_PLAYERS as f32 + 1.0);
+                    Vec2::new(center.x * 0.5, center.y * 2.0 * spread)
+                })
+            }
+        }),
+        on_tick: Box::new(move |state| {
+            let config = config.borrow();
+            let goal = config.as_ref().expect("tick before handshake").field.goal_b();
+            std::array::from_fn(|i| {
+                let player = &state.players[i];
+                let carrying = matches!(
+                    state.ball_possession,
+                    BallPossessionState::Possessed { owner, .. } if owner == player.id
+                );
+                PlayerAction {
+                    dir: (state.ball.pos - player.pos).normalize_or_zero(),
+                    pass: StateOption::None,
+                    tackle: StateOption::None,
+                    shoot: if carrying {
+                        StateOption::Some((goal - player.pos).normalize_or_zero())
+                    } else {
+                        StateOption::None
+                    },
+                    sprint: false,
+                }
+            })
+        }),
+    };
+
+    loop {
+        channel.handle_msg(&strategy).await?;
+    }
+}
@@ -2,46 +2,163 @@ use anyhow::{Context, Result};
 use crate::{
     args::*,
     game::{
-        action::{ eval_reset, eval_tick },
-        state::{ Team ,GameState, PlayerAction, TeamPair, Mirror, mirror_pos },
+        action::{ eval_reset, eval_reset_ball, eval_tick, match_rng },
+        state::{ Team ,GameState, PlayerAction, RefereeEvent, ResetCause, StateOption, TeamPair, Mirror, mirror_pos },
         config::*,
-        util::Vec2
+        sim::match_over,
+        stats::{ MatchStats, MatchResult, MatchSummary, PossessionLog, SeriesTally },
+        util::{ Fnv1a, Vec2 }
     },
     ipc::*,
+    snapshot::{ render_svg, TrailBuffer },
+    replay::replay,
 };
+use rand::Rng;
+use serde::Serialize;
 use simple_moving_average::{SumTreeSMA, SMA};
 use std::{
-    path::Path, process::Stdio, time::{Duration, Instant}
+    net::IpAddr, path::{Path, PathBuf}, process::Stdio, sync::{Arc, OnceLock}, time::{Duration, Instant}
 };
 use tokio::{
     io::{AsyncBufReadExt, BufReader},
     join,
     process::Command,
-    sync::mpsc,
+    sync::{mpsc, watch, Semaphore},
 };
 
-const TOTAL_COMPUTE_TICKS: u32 = 100000;
-const DELAY_TICKS: u32 = 2000;
-const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+pub const TOTAL_COMPUTE_TICKS: u32 = 100000;
+pub const DELAY_TICKS: u32 = 2000;
+pub const HANDSHAKE_TIMEOUT_SECS: u64 = 10;
+
+/// how much time a bot gets to respond, measured in engine ticks
+#[derive(Clone, Copy, Debug)]
+struct ComputeBudget {
+    /// most ticks a bot can bank
+    total: u32,
+    /// ticks a bot can spend on each message without drawing from its bank
+    delay: u32,
+    handshake_timeout: Duration,
+    /// notional engine tick time bot timeouts are measured in, instead of the measured physics time
+    tick_time: Option<Duration>,
+    /// responses are never charged against the bank, see --fast
+    unmetered: bool,
+}
+
+impl ComputeBudget {
+    fn from_args(args: &ArgConfig) -> Self {
+        Self {
+            total: args.compute_budget,
+            delay: args.delay_ticks,
+            handshake_timeout: Duration::from_secs(args.handshake_timeout_secs),
+            tick_time: args.tick_time_us.map(Duration::from_micros),
+            unmetered: args.fast,
+        }
+    }
+
+    /// the tick time bot timeouts and charges are measured in, given the measured physics time
+    fn engine_time(&self, measured: Duration) -> Duration {
+        self.tick_time.unwrap_or(measured)
+    }
+
+    /// a bot's remaining ticks after it took elapsed ticks to respond
+    fn charge(&self, ticks: u32, elapsed: u32) -> u32 {
+        if elapsed <= self.delay {
+            self.total.min(ticks.saturating_add(self.delay - elapsed))
+        } else {
+            ticks - ticks.min(elapsed - self.delay)
+        }
+    }
+}
+
+/// running aggregate of a bot's response times over a match
+#[derive(Clone, Copy, Debug, Default)]
+struct ResponseTimes {
+    count: u32,
+    total: Duration,
+    max: Duration,
+}
+
+impl ResponseTimes {
+    fn add(&mut self, time: Duration) {
+        self.count += 1;
+        self.total += time;
+        self.max = self.max.max(time);
+    }
+
+    fn mean(&self) -> Duration {
+        self.total.checked_div(self.count).unwrap_or_default()
+    }
+}
+
+/// min, max, mean and 95th percentile of the engine's tick evaluation times as one line
+fn tick_time_summary(times: &mut [Duration]) -> Option<String> {
+    times.sort_unstable();
+    let (min, max) = (*times.first()?, *times.last()?);
+    let mean = times.iter().sum::<Duration>() / times.len() as u32;
+    // nearest rank
+    let p95 = times[(times.len() * 95).div_ceil(100) - 1];
+    Some(format!("min {:?} max {:?} mean {:?} p95 {:?} over {} ticks", min, max, mean, p95, times.len()))
+}
+
+static MATCH_LIMIT: OnceLock<Semaphore> = OnceLock::new();
+
+enum BotConnection {
+    Shm(BotChannel),
+    Tcp(TcpBotChannel),
+}
+
+impl Transport for BotConnection {
+    fn endpoint(&self) -> String {
+        match self {
+            Self::Shm(channel) => channel.endpoint(),
+            Self::Tcp(channel) => channel.endpoint(),
+        }
+    }
+
+    async fn msg<T: Protocol>(&mut self, msg: &T::Msg, timeout: Duration) -> ResponseResult<T::Response>
+        where <T as Protocol>::Msg : Clone
+    {
+        match self {
+            Self::Shm(channel) => Transport::msg::<T>(channel, msg, timeout).await,
+            Self::Tcp(channel) => Transport::msg::<T>(channel, msg, timeout).await,
+        }
+    }
+}
 
 struct BotManager {
-    channel: BotChannel,
+    channel: BotConnection,
     name: String,
+    budget: ComputeBudget,
     ticks: u32,
+    idle_ticks: u32,
+    idle_warn_ticks: u32,
+    timeout_policy: TimeoutPolicy,
+    last_actions: [PlayerAction; NUM_PLAYERS as usize],
+    /// reset and tick responses, handshakes are left out
+    response_times: ResponseTimes,
     process: tokio::process::Child,
     io_task: tokio::task::JoinHandle<()>,
 }
 
 impl BotManager {
-    fn spawn(
+    #[allow(clippy::too_many_arguments)]
+    async fn spawn(
         command: &Path,
         name: &str,
         source: OutputSource,
+        transport: TransportKind,
+        tcp_host: IpAddr,
+        budget: ComputeBudget,
+        idle_warn_ticks: u32,
+        output_limit: usize,
         tx: mpsc::UnboundedSender<Message>,
     ) -> anyhow::Result<Self> {
-        let channel = BotChannel::new()?;
+        let channel = match transport {
+            TransportKind::Shm => BotConnection::Shm(BotChannel::new()?),
+            TransportKind::Tcp => BotConnection::Tcp(TcpBotChannel::bind(tcp_host).await?),
+        };
         let mut process = Command::new(command)
-            .arg(channel.backing_file_path())
+            .arg(channel.endpoint())
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
             .spawn()
@@ -54,18 +171,37 @@ impl BotManager {
         let io_task = tokio::spawn(async move {
             let mut stdout_reader = BufReader::new(stdout).lines();
             let mut stderr_reader = BufReader::new(stderr).lines();
+            let mut written = 0;
+            let mut suppressed = false;
+
+            // keep draining after the limit so the bot never blocks on a full pipe
+            let mut within_limit = |len: usize| {
+                written += len;
+                if output_limit == 0 || written <= output_limit {
+                    return true;
+                }
+                if !suppressed {
+                    suppressed = true;
+                    send!(tx, OutputSource::Gamelog, "### [bot {}] output suppressed after {} bytes", &name_async, output_limit);
+                }
+                false
+            };
 
             loop {
                 tokio::select! {
                     line = stdout_reader.next_line() => {
                         match line {
-                            Ok(Some(line)) => send!(tx, source, "#[{}]: {}", &name_async, line),
+                            Ok(Some(line)) => if within_limit(line.len()) {
+                                send!(tx, source, "#[{}]: {}", &name_async, line)
+                            },
                             Ok(None) | Err(_) => break,
                         }
                     }
                     line = stderr_reader.next_line() => {
                         match line {
-                            Ok(Some(line)) => send!(tx, source, "#[{}] ERR: {}", &name_async, line),
+                            Ok(Some(line)) => if within_limit(line.len()) {
+                                send!(tx, source.stderr(), "#[{}] ERR: {}", &name_async, line)
+                            },
                             Ok(None) | Err(_) => break,
                         }
                     }
@@ -76,19 +212,52 @@ impl BotManager {
         Ok(Self {
             channel,
             name: name.to_string(),
-            ticks: TOTAL_COMPUTE_TICKS,
+            budget,
+            ticks: budget.total,
+            idle_ticks: 0,
+            idle_warn_ticks,
+            timeout_policy: TimeoutPolicy::Stop,
+            last_actions: Default::default(),
+            response_times: ResponseTimes::default(),
             process,
             io_task,
         })
     }
 
     fn exited(&mut self) -> bool {
-        self.process.try_wait().map_or(true, |status| status.is_some())
+        self.exit_reason().is_some()
+    }
+
+    /// how the bot process ended, if it has
+    fn exit_reason(&mut self) -> Option<String> {
+        match self.process.try_wait() {
+            Ok(None) => None,
+            Ok(Some(status)) => Some(status.to_string()),
+            Err(e) => Some(e.to_string()),
+        }
+    }
+
+    /// sends msg over the channel, giving up as soon as the bot process exits instead of waiting
+    /// out the timeout
+    async fn request<T: Protocol>(&mut self, msg: &T::Msg, timeout: Duration) -> ResponseResult<T::Response>
+        where <T as Protocol>::Msg : Clone
+    {
+        tokio::select! {
+            res = self.channel.msg::<T>(msg, timeout) => res,
+            status = self.process.wait() => Err(ResponseError::Connection(match status {
+                Ok(status) => std::io::Error::other(format!("bot exited ({status})")),
+                Err(e) => e,
+            })),
+        }
     }
 
     async fn handshake(&mut self, team: Team, config: &GameConfig, tx: &mpsc::UnboundedSender<Message>) {
-        if !self.channel
-            .msg::<HandshakeProtocol>(&HandshakeMsg { team, config: config.clone() }, HANDSHAKE_TIMEOUT)
+        self.timeout_policy = config.timeout_policy;
+        if !self
+            .request::<HandshakeProtocol>(
+                &HandshakeMsg { team, version: PROTOCOL_VERSION, config: config.clone() },
+                self.budget.handshake_timeout,
+            )
             .await
             .map_err(|e| {
                 self.ticks = 0;
@@ -104,20 +273,29 @@ impl BotManager {
             })
             .ok()
             .map(|res| {
-                let matches = res == HANDSHAKE_BOT;
-                if !matches {
+                let reason = if res.magic != HANDSHAKE_BOT {
+                    Some(format!("expected {}, got {}", HANDSHAKE_BOT, res.magic))
+                } else if res.version != PROTOCOL_VERSION {
+                    Some(format!(
+                        "bot speaks protocol version {}, engine speaks version {}",
+                        res.version,
+                        PROTOCOL_VERSION
+                    ))
+                } else {
+                    None
+                };
+                if let Some(reason) = &reason {
                     self.ticks = 0;
-                    eprintln!("### FATAL ERROR: bot {} failed handshake: expected {}, got {}", self.name, HANDSHAKE_BOT, res);
+                    eprintln!("### FATAL ERROR: bot {} failed handshake: {}", self.name, reason);
                     send!(
                         tx,
                         OutputSource::Gamelog,
-                        "### FATAL ERROR: bot {} failed handshake: expected {}, got {}",
+                        "### FATAL ERROR: bot {} failed handshake: {}",
                         self.name,
-                        HANDSHAKE_BOT,
-                        res
+                        reason
                     );
                 }
-                matches
+                reason.is_none()
             })
             .unwrap_or(false)
         {
@@ -125,17 +303,20 @@ impl BotManager {
         }
     }
 
-    async fn reset(&mut self, score: &TeamPair<u32>, engine_time: Duration, tx: &mpsc::UnboundedSender<Message>) -> [Vec2; NUM_PLAYERS as usize] {
+    async fn reset(&mut self, score: &TeamPair<u32>, cause: ResetCause, engine_time: Duration, tx: &mpsc::UnboundedSender<Message>) -> [Vec2; NUM_PLAYERS as usize] {
 
+        self.last_actions = Default::default();
         if self.exited() {
             return Default::default();
         }
 
         let time = Instant::now();
-        let res = self.channel
-            .msg::<ResetProtocol>(score, self.ticks * engine_time)
+        let mut lost = false;
+        let res = self
+            .request::<ResetProtocol>(&ResetMsg { score: *score, cause }, self.ticks * engine_time)
             .await
             .unwrap_or_else(|e| {
+                lost = matches!(e, ResponseError::Connection(_));
                 eprintln!("### [bot {}] error resetting: {e}", self.name);
                 send!(
                     tx,
@@ -145,15 +326,43 @@ impl BotManager {
                 );
                 Default::default()
             });
-        let elapsed = time.elapsed().div_duration_f64(engine_time) as u32;
-        self.ticks = if elapsed <= DELAY_TICKS {
-            TOTAL_COMPUTE_TICKS.min(self.ticks + DELAY_TICKS - elapsed)
-        } else {
-            self.ticks - self.ticks.min(elapsed - DELAY_TICKS)
-        };
+        if lost {
+            self.disconnect(tx);
+        }
+        let response_time = time.elapsed();
+        self.response_times.add(response_time);
+        if !self.budget.unmetered {
+            let elapsed = response_time.div_duration_f64(engine_time) as u32;
+            self.ticks = self.budget.charge(self.ticks, elapsed);
+        }
         res
     }
 
+    /// a broken connection can't recover, so the bot is stopped and forfeits like a crashed one
+    fn disconnect(&mut self, tx: &mpsc::UnboundedSender<Message>) {
+        eprintln!("### [bot {}] connection lost, stopping the bot", self.name);
+        send!(tx, OutputSource::Gamelog, "### [bot {}] connection lost, stopping the bot", self.name);
+        let _ = self.process.start_kill();
+    }
+
+    fn track_idle(&mut self, actions: &[PlayerAction], tx: &mpsc::UnboundedSender<Message>) {
+        if !actions.iter().all(|a| *a == PlayerAction::default()) {
+            self.idle_ticks = 0;
+            return;
+        }
+        self.idle_ticks += 1;
+        if self.idle_ticks == self.idle_warn_ticks {
+            eprintln!("### [bot {}] warning: no actions for {} ticks, bot may be stuck or erroring", self.name, self.idle_ticks);
+            send!(
+                tx,
+                OutputSource::Gamelog,
+                "### [bot {}] warning: no actions for {} ticks, bot may be stuck or erroring",
+                self.name,
+                self.idle_ticks
+            );
+        }
+    }
+
     async fn tick(&mut self, state: &GameState, engine_time: Duration, tx: &mpsc::UnboundedSender<Message>) -> [PlayerAction; NUM_PLAYERS as usize] {
 
         if self.exited() {
@@ -161,10 +370,12 @@ impl BotManager {
         }
 
         let time = Instant::now();
-        let res = self.channel
-            .msg::<TickProtocol>(state, self.ticks * engine_time)
+        let mut lost = false;
+        let res = self
+            .request::<TickProtocol>(state, self.ticks * engine_time)
             .await
             .unwrap_or_else(|e| {
+                lost = matches!(e, ResponseError::Connection(_));
                 eprintln!("### [bot {}] error on tick: {e}", self.name);
                 send!(
                     tx,
@@ -172,107 +383,448 @@ impl BotManager {
                     "### [bot {}] error on tick: {e}",
                     self.name
                 );
-                Default::default()
+                match (e, self.timeout_policy) {
+                    (ResponseError::Timeout(_), TimeoutPolicy::Coast) => self.last_actions.clone(),
+                    _ => Default::default(),
+                }
             });
-        let elapsed = time.elapsed().div_duration_f64(engine_time) as u32;
-        // println!("bot {} took {} ticks", self.name, elapsed);
-        self.ticks = if elapsed <= DELAY_TICKS {
-            TOTAL_COMPUTE_TICKS.min(self.ticks + DELAY_TICKS - elapsed)
-        } else {
-            self.ticks - self.ticks.min(elapsed - DELAY_TICKS)
-        };
+        if lost {
+            self.disconnect(tx);
+        }
+        self.last_actions = res.clone();
+        self.track_idle(&res, tx);
+        let response_time = time.elapsed();
+        self.response_times.add(response_time);
+        if !self.budget.unmetered {
+            let elapsed = response_time.div_duration_f64(engine_time) as u32;
+            // println!("bot {} took {} ticks", self.name, elapsed);
+            self.ticks = self.budget.charge(self.ticks, elapsed);
+        }
         res
     }
 }
 
 
+/// one team's actions for a tick in a single line, e.g. `t=42 p0:0.8 p1:0.0 p2:pass`. a kick is
+/// shown in place of the movement magnitude
+pub fn input_summary(tick: u32, actions: &[PlayerAction]) -> String {
+    let mut line = format!("t={tick}");
+    for (i, action) in actions.iter().enumerate() {
+        let input = match (&action.shoot, &action.pass, &action.tackle) {
+            (StateOption::Some(_), _, _) => "shoot".to_string(),
+            (_, StateOption::Some(_), _) => "pass".to_string(),
+            (_, _, StateOption::Some(_)) => "tackle".to_string(),
+            _ => format!("{:.1}", action.dir.norm()),
+        };
+        line += &format!(" p{i}:{input}{}", if action.sprint { "+" } else { "" });
+    }
+    line
+}
+
+/// the state as logged, with every float rounded to decimals places when given
+pub fn state_json(state: &GameState, decimals: Option<u32>) -> serde_json::Result<String> {
+    let Some(decimals) = decimals else {
+        return serde_json::to_string(state);
+    };
+    let mut out = Vec::new();
+    let formatter = RoundingFormatter { scale: 10f64.powi(decimals as i32) };
+    state.serialize(&mut serde_json::Serializer::with_formatter(&mut out, formatter))?;
+    // serde_json only writes valid utf8
+    Ok(String::from_utf8(out).unwrap())
+}
+
+struct RoundingFormatter {
+    scale: f64,
+}
+
+impl serde_json::ser::Formatter for RoundingFormatter {
+    fn write_f32<W: ?Sized + std::io::Write>(&mut self, writer: &mut W, value: f32) -> std::io::Result<()> {
+        // rounded in f64 so the result is the f32 closest to the decimal, which prints as it
+        let rounded = ((value as f64 * self.scale).round() / self.scale) as f32;
+        serde_json::ser::CompactFormatter.write_f32(writer, rounded)
+    }
+
+    fn write_f64<W: ?Sized + std::io::Write>(&mut self, writer: &mut W, value: f64) -> std::io::Result<()> {
+        serde_json::ser::CompactFormatter.write_f64(writer, (value * self.scale).round() / self.scale)
+    }
+}
+
+fn send_referee(tx: &mpsc::UnboundedSender<Message>, event: RefereeEvent) -> Result<()> {
+    send!(tx, OutputSource::Gamelog, LineKind::Referee; "{}", serde_json::to_string(&event)?);
+    Ok(())
+}
+
+/// a repeating event is logged at most once per this many ticks
+const REPEAT_LOG_TICKS: u32 = 60;
+
+/// keeps events that repeat every tick their condition holds from flooding the gamelog. every
+/// other event always passes
+#[derive(Default)]
+struct EventLimiter {
+    /// each distinct repeating event and the tick it was last logged
+    logged: Vec<(RefereeEvent, u32)>,
+}
+
+impl EventLimiter {
+    fn allow(&mut self, event: &RefereeEvent, tick: u32) -> bool {
+        if !event.repeats() {
+            return true;
+        }
+        match self.logged.iter_mut().find(|(logged, _)| logged.same_call(event)) {
+            Some((_, last)) if tick < *last + REPEAT_LOG_TICKS => false,
+            Some((_, last)) => {
+                *last = tick;
+                true
+            }
+            None => {
+                self.logged.push((*event, tick));
+                true
+            }
+        }
+    }
+}
+
+/// deterministic id derived from the bot paths, the config and the seed
+pub fn match_id(args: &ArgConfig, conf: &GameConfig, seed: u64) -> Result<String> {
+    let mut hash = Fnv1a::default();
+    hash.feed(args.bot_a.as_deref().unwrap_or(Path::new("")).as_os_str().as_encoded_bytes());
+    hash.feed(&[0]);
+    hash.feed(args.bot_b.as_deref().unwrap_or(Path::new("")).as_os_str().as_encoded_bytes());
+    hash.feed(&[0]);
+    hash.feed(serde_json::to_string(conf)?.as_bytes());
+    hash.feed(&seed.to_le_bytes());
+    Ok(format!("{:016x}", hash.0))
+}
+
 pub async fn run(args: ArgConfig) -> Result<()> {
-    let (tx, recv_task) = spawn_reciever(&args)?;
-
-    let conf = GameConfig {
-        max_ticks: 7200,
-        endgame_ticks: 1000,
-        spawn_ball_dist: 200.0,
-        ball: BallConfig {
-            friction: 0.99,
-            radius: 5.0,
-            capture_ticks: 50,
-            stagnation_radius: 30.0,
-            stagnation_ticks: 150,
-        },
-        player: PlayerConfig {
-            radius: 10.0,
-            pickup_radius: 25.0,
-            speed: 4.0,
-            pass_speed: 12.0,
-            pass_error: 10.0,
-            possession_slowdown: 0.75,
-        },
-        field: FieldConfig {
-            width: 1000,
-            height: 600,
-        },
-        goal: GoalConfig {
-            normal_height: 150,
-            thickness: 5,
-            penalty_box_width: 91,
-            penalty_box_height: 250,
-            penalty_box_radius: 24,
+    let (tx, recv_task) = spawn_reciever(&args).await?;
+
+    // ctrl-c ends the match through the normal shutdown path, so the bots are killed and every
+    // buffered line reaches the output files. a second ctrl-c exits immediately
+    let cancel = CancelToken::default();
+    let interrupt_task = tokio::spawn({
+        let cancel = cancel.clone();
+        async move {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                eprintln!("### interrupted, finishing the current tick");
+                cancel.cancel();
+            }
+            if tokio::signal::ctrl_c().await.is_ok() {
+                std::process::exit(130);
+            }
+        }
+    });
+
+    let result = match &args.replay {
+        Some(path) => replay(path, &tx)
+            .and_then(|result| result.context("replay ends before the match result"))
+            .map(Outcome::Match),
+        None if args.games > 1 => run_series(&args, &tx, &cancel).await.map(Outcome::Series),
+        None => run_match(&args, &tx, &cancel).await.map(Outcome::Match),
+    };
+    interrupt_task.abort();
+
+    // engine failures get a machine readable record so harnesses can tell them apart from results
+    if let Err(e) = &result {
+        send!(
+            tx,
+            OutputSource::Gamelog,
+            LineKind::Fatal;
+            "{}",
+            serde_json::json!({
+                "fatal": e.to_string(),
+                "cause": e.root_cause().to_string(),
+            })
+        );
+    }
+
+    drop(tx);
+    let _ = recv_task.await;
+
+    let result = match result? {
+        Outcome::Match(result) => result,
+        Outcome::Series(tally) => {
+            let teams = args.teams();
+            println!(
+                "# Series of {} games: Bot A ({}) won {}, Bot B ({}) won {}, {} tied, {} cancelled\n# Goals: A: {} B: {} (difference {:+})",
+                tally.games,
+                teams.a.name,
+                tally.wins.a,
+                teams.b.name,
+                tally.wins.b,
+                tally.ties,
+                tally.cancelled,
+                tally.goals.a,
+                tally.goals.b,
+                tally.goal_difference(),
+            );
+            return Ok(());
+        }
+    };
+
+    let teams = &result.teams;
+    println!("# Final Score: A ({}): {} B ({}): {}\n# {}", 
+        teams.a.name,
+        result.score.a,
+        teams.b.name,
+        result.score.b,
+        match (result.winner, result.decided_by) {
+            (Some(winner), Some(decided_by)) => format!(
+                "The winner is Bot {} ({}) (decided by {})",
+                if winner == Team::A { "A" } else { "B" },
+                teams[winner].name,
+                decided_by
+            ),
+            _ if result.cancelled => "The match was CANCELLED".to_string(),
+            _ => "The match was a TIE".to_string()
+        }
+    );
+
+    Ok(())
+}
+
+enum Outcome {
+    Match(MatchResult),
+    Series(SeriesTally),
+}
+
+/// the path of game's output file in a series, e.g. summary.json becomes summary-2.json
+fn game_path(path: &Path, game: u32) -> PathBuf {
+    let mut name = path.file_stem().unwrap_or_default().to_os_string();
+    name.push(format!("-{}", game));
+    if let Some(ext) = path.extension() {
+        name.push(".");
+        name.push(ext);
+    }
+    path.with_file_name(name)
+}
+
+/// plays args.games matches between the same bots, swapping their sides every other match to
+/// cancel out any side bias. each match starts from a fresh state and freshly spawned bots. with
+/// a fixed seed, match i uses seed + i. given several configs, match i plays config i mod count
+/// and its handshake sends the bots that config. the snapshot, summary and possession log of
+/// match i go to their paths suffixed with -i. cancelling ends the series after the current match
+pub async fn run_series(args: &ArgConfig, tx: &mpsc::UnboundedSender<Message>, cancel: &CancelToken) -> Result<SeriesTally> {
+    let mut tally = SeriesTally::default();
+    for game in 0..args.games {
+        if cancel.is_cancelled() {
+            break;
+        }
+        let swapped = game % 2 == 1;
+        let mut game_args = args.clone();
+        if swapped {
+            std::mem::swap(&mut game_args.bot_a, &mut game_args.bot_b);
+            std::mem::swap(&mut game_args.name_a, &mut game_args.name_b);
+            std::mem::swap(&mut game_args.color_a, &mut game_args.color_b);
+            game_args.start_score = TeamPair::new(args.start_score.b, args.start_score.a);
+        }
+        game_args.seed = args.seed.map(|seed| seed.wrapping_add(game as u64));
+        if !args.config.is_empty() {
+            game_args.config = vec![args.config[game as usize % args.config.len()].clone()];
+        }
+        if args.games > 1 {
+            for path in [&mut game_args.snapshot, &mut game_args.summary, &mut game_args.possession_log] {
+                *path = path.as_deref().map(|path| game_path(path, game + 1));
+            }
+        }
+
+        send!(
+            tx,
+            OutputSource::Gamelog,
+            "# game {} of {}: Bot A plays as team {}",
+            game + 1,
+            args.games,
+            if swapped { "B" } else { "A" }
+        );
+        let result = run_match(&game_args, tx, cancel).await
+            .with_context(|| format!("game {} of {} failed", game + 1, args.games))?;
+        tally.add(&result, swapped);
+    }
+    send!(tx, OutputSource::Gamelog, "# series: {}", serde_json::to_string(&tally)?);
+    Ok(tally)
+}
+
+/// caps how many matches can run at once across the process. can only be set once,
+/// returns false if a limit was already set
+pub fn limit_concurrent_matches(max: usize) -> bool {
+    MATCH_LIMIT.set(Semaphore::new(max)).is_ok()
+}
+
+/// handle for stopping a running match early
+#[derive(Clone)]
+pub struct CancelToken(Arc<watch::Sender<bool>>);
+
+impl Default for CancelToken {
+    fn default() -> Self {
+        Self(Arc::new(watch::Sender::new(false)))
+    }
+}
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.0.send_replace(true);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        *self.0.borrow()
+    }
+
+    pub async fn cancelled(&self) {
+        let _ = self.0.subscribe().wait_for(|cancelled| *cancelled).await;
+    }
+}
+
+/// the config line of the gamelog. carries the seed so any match can be rerun with --seed, and
+/// still loads as a config file
+#[derive(Serialize)]
+struct ConfigHeader<'a> {
+    seed: u64,
+    #[serde(flatten)]
+    config: &'a GameConfig,
+}
+
+/// play a single match, sending output to tx. waits for a slot if a concurrency limit is set.
+/// cancelling stops the match after killing the bots and returns a cancelled result
+pub async fn run_match(args: &ArgConfig, tx: &mpsc::UnboundedSender<Message>, cancel: &CancelToken) -> Result<MatchResult> {
+    let _permit = match MATCH_LIMIT.get() {
+        Some(limit) => tokio::select! {
+            permit = limit.acquire() => Some(permit?),
+            _ = cancel.cancelled() => return Ok(MatchResult::cancelled("", 0, &TeamPair::default())),
         },
+        None => None,
     };
 
+    let mut conf = match args.config.first() {
+        Some(path) => GameConfig::load(path)?,
+        None => GameConfig::default(),
+    };
+    // sent to the bots as part of the config, so they know the match can end tied
+    if args.no_overtime {
+        conf.endgame_ticks = 0;
+    }
+
+    let seed = args.seed.unwrap_or_else(|| rand::rng().random());
+    let mut rng = match_rng(seed);
     send!(
         tx,
         OutputSource::Gamelog,
+        LineKind::Config;
         "{}",
-        serde_json::to_string(&conf)?
+        serde_json::to_string(&ConfigHeader { seed, config: &conf })?
     );
+    send!(tx, OutputSource::Gamelog, "# seed: {}", seed);
 
+    let match_id = match_id(args, &conf, seed)?;
+    send!(tx, OutputSource::Gamelog, "# match id: {}", match_id);
+
+    let teams = args.teams();
+    send!(tx, OutputSource::Gamelog, "# team A: {} ({})", teams.a.name, teams.a.color);
+    send!(tx, OutputSource::Gamelog, "# team B: {} ({})", teams.b.name, teams.b.color);
+
+    let (path_a, path_b) = (
+        args.bot_a.as_deref().context("no path given for bot a")?,
+        args.bot_b.as_deref().context("no path given for bot b")?,
+    );
+    let budget = ComputeBudget::from_args(args);
+    let spawn = |path, name, source| BotManager::spawn(
+        path, name, source, args.transport, args.tcp_host, budget, args.idle_warn_ticks, args.bot_output_limit, tx.clone()
+    );
+    // the side stays in the tag, since both names default to the file name and often match
     let (mut bot_a, mut bot_b) = (
-        BotManager::spawn(&args.bot_a, "A", OutputSource::BotA, tx.clone())?,
-        BotManager::spawn(&args.bot_b, "B", OutputSource::BotB, tx.clone())?,
+        spawn(path_a, &format!("A:{}", teams.a.name), OutputSource::BotA).await?,
+        spawn(path_b, &format!("B:{}", teams.b.name), OutputSource::BotB).await?,
     );
 
     let start = Instant::now();
-    join!(bot_a.handshake(Team::A, &conf, &tx), bot_b.handshake(Team::B, &conf, &tx));
+    // a cancel here falls through to the loop, which ends the match before the first tick
+    tokio::select! {
+        _ = async { join!(bot_a.handshake(Team::A, &conf, tx), bot_b.handshake(Team::B, &conf, tx)) } => {},
+        _ = cancel.cancelled() => {},
+    }
     let mut ma = SumTreeSMA::<_, _, 50>::from_zero(Duration::from_millis(1));
+    let mut tick_times = Vec::new();
 
     let mut state = GameState::new(&conf);
-    let mut needs_reset = true;
+    state.score = args.start_score;
+    let mut stats = MatchStats::default();
+    let mut possession_log = PossessionLog::default();
+    let mut trails = TrailBuffer::new(if args.snapshot.is_some() { args.snapshot_trail } else { 0 });
+    let mut needs_reset = Some(ResetCause::NewGame);
     let mut endgame_reset = false;
+    let mut forfeited = None;
+    // every logged state in order, so a rerun or replay of the same match ends with the same value
+    let mut checksum = Fnv1a::default();
+    let mut event_limiter = EventLimiter::default();
+
+    while !match_over(&state, &conf) {
+        if cancel.is_cancelled() {
+            break;
+        }
 
-    while state.tick < conf.max_ticks || (state.tick < (conf.max_ticks + conf.endgame_ticks) && state.score.a == state.score.b) {
-        let last_tick_time = ma.get_average();
+        // bots only exit on their own mid match when they crash, playing on without them is pointless
+        let exits = TeamPair::new(bot_a.exit_reason(), bot_b.exit_reason());
+        if exits.a.is_some() || exits.b.is_some() {
+            for (name, reason) in [("A", &exits.a), ("B", &exits.b)] {
+                if let Some(reason) = reason {
+                    send!(tx, OutputSource::Gamelog, "# Bot {} forfeited at tick {}: {}", name, state.tick, reason);
+                }
+            }
+            forfeited = Some(TeamPair::new(exits.a.is_some(), exits.b.is_some()));
+            break;
+        }
+        let frame_start = Instant::now();
+        let last_tick_time = budget.engine_time(ma.get_average());
 
         // call reset during endgame
         if !endgame_reset && state.tick >= conf.max_ticks {
-            needs_reset = true;
+            needs_reset = Some(ResetCause::Endgame);
             endgame_reset = true;
         }
 
-        if needs_reset {
+        if needs_reset.is_some_and(|cause| conf.ball_only_reset(cause)) {
+            eval_reset_ball(&mut state, &conf);
+        } else if let Some(cause) = needs_reset {
             let mut mirrored_score = state.score;
             mirrored_score.mirror(&conf);
-            let (formation_a, mut formation_b) = (
-                bot_a.reset(&state.score, last_tick_time, &tx).await, 
-                bot_b.reset(&mirrored_score, last_tick_time, &tx).await
-            );
+            let (formation_a, mut formation_b) = tokio::select! {
+                formations = async { (
+                    bot_a.reset(&state.score, cause, last_tick_time, tx).await,
+                    bot_b.reset(&mirrored_score, cause, last_tick_time, tx).await
+                ) } => formations,
+                _ = cancel.cancelled() => break,
+            };
             formation_b.iter_mut().for_each(|pos| mirror_pos(pos, &conf));
             let formation = TeamPair::new(formation_a, formation_b);
             eval_reset(&mut state, &conf, &formation);
+            send_referee(tx, RefereeEvent::KickoffReset)?;
         }
 
+        if conf.expose_opponent_compute {
+            state.opponent_compute = StateOption::Some(bot_b.ticks);
+        }
         let mut mirrored_state = state.clone();
         mirrored_state.mirror(&conf);
+        if conf.expose_opponent_compute {
+            mirrored_state.opponent_compute = StateOption::Some(bot_a.ticks);
+        }
 
-        let (mut action_a, mut action_b) = join!(
-            bot_a.tick(&state, last_tick_time, &tx), 
-            bot_b.tick(&mirrored_state, last_tick_time, &tx)
-        );
+        let (mut action_a, mut action_b) = tokio::select! {
+            actions = async { join!(
+                bot_a.tick(&state, last_tick_time, tx), 
+                bot_b.tick(&mirrored_state, last_tick_time, tx)
+            ) } => actions,
+            _ = cancel.cancelled() => break,
+        };
+        state.opponent_compute = StateOption::None;
 
         action_a.iter_mut().for_each(|a| a.sanitize());
         action_b.iter_mut().for_each(|a| a.sanitize());
 
+        if let Some(team) = args.debug_inputs {
+            let actions = if team == Team::A { &action_a } else { &action_b };
+            send!(tx, OutputSource::Gamelog, "# inputs {}", input_summary(state.tick, actions));
+        }
+
         let actions = std::array::from_fn(|i| {
             if i < NUM_PLAYERS as usize {
                 action_a[i].clone()
@@ -283,53 +835,223 @@ pub async fn run(args: ArgConfig) -> Result<()> {
             }
         });
 
+        let prev_possession = state.ball_possession.clone();
         let tick_start = Instant::now();
-        needs_reset = eval_tick(&mut state, &conf, actions);
-        ma.add_sample(tick_start.elapsed());
+        let mut events = Vec::new();
+        needs_reset = eval_tick(&mut state, &conf, actions, &mut rng, &mut events);
+        let tick_time = tick_start.elapsed();
+        ma.add_sample(tick_time);
+        tick_times.push(tick_time);
+        stats.update(&prev_possession, &state, &conf);
+        if args.possession_log.is_some() {
+            possession_log.update(&prev_possession, &state);
+        }
+        trails.push(&state);
+        checksum.feed(&state.state_hash().to_le_bytes());
 
         send!(
             tx,
             OutputSource::Gamelog,
+            LineKind::State;
             "{}",
-            serde_json::to_string(&state)?
+            state_json(&state, args.decimals)?
         );
+        for event in events {
+            if event_limiter.allow(&event, state.tick) {
+                send!(tx, OutputSource::Gamelog, "# {}", event);
+                send_referee(tx, event)?;
+            }
+        }
+
+        // pacing only, the simulation and the bots' budgets are untouched
+        if args.slowmo > 1.0 {
+            let delay = frame_start.elapsed().mul_f32(args.slowmo - 1.0);
+            tokio::select! {
+                _ = tokio::time::sleep(delay) => {},
+                _ = cancel.cancelled() => break,
+            }
+        }
     }
 
-    let winner = if state.score.a > state.score.b {
-        Some("Bot A")
-    } else if state.score.a < state.score.b {
-        Some("Bot B")
+    let result = if cancel.is_cancelled() {
+        send!(tx, OutputSource::Gamelog, "# match cancelled at tick {}", state.tick);
+        MatchResult::cancelled(&match_id, seed, &state.score)
+    } else if let Some(forfeited) = forfeited {
+        MatchResult::forfeit(&match_id, seed, &state.score, forfeited, args.forfeit_score)
     } else {
-        None
+        MatchResult::decide(&match_id, seed, &state.score, &stats, &args.tie_break, &mut rng)
     };
+    let result = MatchResult { teams: teams.clone(), checksum: format!("{:016x}", checksum.0), ..result };
+
+    if !result.cancelled {
+        send_referee(tx, RefereeEvent::GameOver { winner: result.winner })?;
+    }
+
+    if let Some(path) = &args.snapshot {
+        std::fs::write(path, render_svg(&state, &conf, &teams, &trails))
+            .with_context(|| format!("failed to write snapshot to {}", path.display()))?;
+    }
+
+    if let Some(path) = &args.possession_log {
+        std::fs::write(path, serde_json::to_string(&possession_log.events)?)
+            .with_context(|| format!("failed to write possession log to {}", path.display()))?;
+    }
+
+    let elapsed = start.elapsed();
+    if let Some(path) = &args.summary {
+        let summary = MatchSummary {
+            result: result.clone(),
+            ticks: state.tick,
+            elapsed_secs: elapsed.as_secs_f64(),
+            stats,
+            config: conf,
+        };
+        std::fs::write(path, serde_json::to_string(&summary)?)
+            .with_context(|| format!("failed to write summary to {}", path.display()))?;
+    }
+
+    send!(
+        tx,
+        OutputSource::Gamelog,
+        LineKind::Result;
+        "{}",
+        serde_json::to_string(&result)?
+    );
 
     send!(
         tx,
         OutputSource::Gamelog,
         "# time elapsed: {:?}",
-        start.elapsed()
+        elapsed
     );
+    if let Some(summary) = tick_time_summary(&mut tick_times) {
+        send!(tx, OutputSource::Gamelog, "# engine tick time: {}", summary);
+    }
+    for bot in [&bot_a, &bot_b] {
+        let times = &bot.response_times;
+        if times.count > 0 {
+            send!(
+                tx,
+                OutputSource::Gamelog,
+                "# bot {} response time: mean {:?} max {:?} over {} responses",
+                bot.name,
+                times.mean(),
+                times.max,
+                times.count
+            );
+        }
+    }
 
     bot_a.io_task.abort();
     bot_b.io_task.abort();
 
     let _ = join!(bot_a.process.kill(), bot_b.process.kill());
 
-    drop(tx);
-    drop(bot_a);
-    drop(bot_b);
+    Ok(result)
+}
 
-    let _ = recv_task.await;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    println!("# Final Score: A: {} B: {}\n# {}", 
-        state.score.a,
-        state.score.b,
-        if let Some(winner) = winner {
-            format!("The winner is {}", winner)
-        } else {
-            "The match was a TIE".to_string()
-        }
-    );
+    #[test]
+    fn repeating_events_are_rate_limited() {
+        let mut limiter = EventLimiter::default();
+        let hoarding = RefereeEvent::Hoarding { team: Team::A, players: 3 };
+        let logged = (0..REPEAT_LOG_TICKS * 2).filter(|tick| limiter.allow(&hoarding, *tick)).count();
+        assert_eq!(logged, 2);
 
-    Ok(())
+        // a different repeating event has its own window
+        assert!(limiter.allow(&RefereeEvent::Hoarding { team: Team::B, players: 3 }, 1));
+
+        // a pileup is rate limited however its overlap changes
+        assert!(limiter.allow(&RefereeEvent::CollisionsUnresolved { iterations: 100, overlap: 2.0 }, 1));
+        assert!(!limiter.allow(&RefereeEvent::CollisionsUnresolved { iterations: 100, overlap: 3.0 }, 2));
+
+        // one-off events always pass
+        let tackle = RefereeEvent::Tackle { tackler: 1, carrier: 5 };
+        assert!((0..10).all(|tick| limiter.allow(&tackle, tick)));
+    }
+
+    fn budget(total: u32, delay: u32) -> ComputeBudget {
+        ComputeBudget { total, delay, handshake_timeout: Duration::ZERO, tick_time: None, unmetered: false }
+    }
+
+    #[test]
+    fn budget_charges_only_past_the_delay() {
+        let budget = budget(1000, 10);
+        // a quick response banks the unused delay, up to the total
+        assert_eq!(budget.charge(500, 4), 506);
+        assert_eq!(budget.charge(998, 0), 1000);
+        // a slow response draws the overrun from the bank, down to zero
+        assert_eq!(budget.charge(500, 30), 480);
+        assert_eq!(budget.charge(5, 30), 0);
+        assert_eq!(budget.charge(500, 10), 500);
+    }
+
+    #[test]
+    fn notional_tick_time_ignores_the_physics() {
+        let measured = budget(1000, 10);
+        let fast = Duration::from_micros(10);
+        assert_eq!(measured.engine_time(fast), fast);
+
+        let fixed = ComputeBudget { tick_time: Some(Duration::from_millis(1)), ..measured };
+        assert_eq!(fixed.engine_time(fast), Duration::from_millis(1));
+        assert_eq!(fixed.engine_time(Duration::from_millis(20)), Duration::from_millis(1));
+    }
+
+    #[test]
+    fn budget_saturates_at_the_limits() {
+        let budget = budget(u32::MAX, u32::MAX);
+        assert_eq!(budget.charge(u32::MAX, 0), u32::MAX);
+        assert_eq!(budget.charge(u32::MAX - 1, 1), u32::MAX);
+        assert_eq!(budget.charge(0, u32::MAX), 0);
+    }
+
+    #[test]
+    fn input_summary_reads_each_player() {
+        let actions = [
+            PlayerAction { dir: Vec2::new(0.8, 0.0), ..Default::default() },
+            PlayerAction::default(),
+            PlayerAction { dir: Vec2::new(1.0, 0.0), pass: StateOption::Some(Vec2::new(1.0, 0.0)), ..Default::default() },
+            PlayerAction { dir: Vec2::new(0.3, 0.4), sprint: true, ..Default::default() },
+        ];
+        assert_eq!(input_summary(42, &actions), "t=42 p0:0.8 p1:0.0 p2:pass p3:0.5+");
+    }
+
+    #[test]
+    fn logged_states_are_rounded() {
+        let conf = GameConfig::default();
+        let mut state = GameState::new(&conf);
+        state.ball.pos = Vec2::new(123.4567, 0.5);
+        let logged: serde_json::Value = serde_json::from_str(&state_json(&state, Some(2)).unwrap()).unwrap();
+        assert_eq!(logged["ball"]["pos"]["x"].to_string(), "123.46");
+        assert_eq!(logged["ball"]["pos"]["y"].to_string(), "0.5");
+        // rounding is only in the log
+        assert_eq!(state.ball.pos.x, 123.4567);
+        let full: serde_json::Value = serde_json::from_str(&state_json(&state, None).unwrap()).unwrap();
+        assert_ne!(full["ball"]["pos"]["x"].to_string(), "123.46");
+    }
+
+    #[test]
+    fn match_id_follows_the_inputs() {
+        use clap::Parser;
+        let conf = GameConfig::default();
+        let args = ArgConfig::try_parse_from(["mm-engine", "bots/a", "bots/b"]).unwrap();
+        let id = match_id(&args, &conf, 1).unwrap();
+        assert_eq!(match_id(&args, &conf, 1).unwrap(), id);
+        assert_ne!(match_id(&args, &conf, 2).unwrap(), id);
+
+        let swapped = ArgConfig::try_parse_from(["mm-engine", "bots/b", "bots/a"]).unwrap();
+        assert_ne!(match_id(&swapped, &conf, 1).unwrap(), id);
+        let longer = GameConfig { max_ticks: conf.max_ticks + 1, ..conf };
+        assert_ne!(match_id(&args, &longer, 1).unwrap(), id);
+    }
+
+    #[test]
+    fn series_outputs_get_the_game_number() {
+        assert_eq!(game_path(Path::new("out/summary.json"), 2), Path::new("out/summary-2.json"));
+        assert_eq!(game_path(Path::new("snapshot"), 1), Path::new("snapshot-1"));
+        assert_eq!(game_path(Path::new("a.log.json"), 10), Path::new("a.log-10.json"));
+    }
 }
@@ -1,31 +1,31 @@
-use std::cell::RefCell;
-
-use super::{config::*, state::*, util::*};
+use super::{config::*, state::*, stats::is_shot, util::*};
 use rand::{prelude::*, seq::SliceRandom};
 
-thread_local! {
-    static RNG: RefCell<SmallRng> = RefCell::new(SmallRng::from_rng(&mut rand::rng()));
-}
+/// randomness for a single match. each match owns its stream, so matches sharing a thread or
+/// process never perturb each other
+pub type MatchRng = SmallRng;
 
-fn with_rng<T>(f: impl FnOnce(&mut SmallRng) -> T) -> T {
-    RNG.with(|rng| f(&mut rng.borrow_mut()))
+pub fn match_rng(seed: u64) -> MatchRng {
+    MatchRng::seed_from_u64(seed)
 }
 
 #[allow(dead_code)]
-fn rand_player_iter<'a>(players: &'a [PlayerState]) -> std::vec::IntoIter<&'a PlayerState> {
+fn rand_player_iter<'a>(players: &'a [PlayerState], rng: &mut MatchRng) -> std::vec::IntoIter<&'a PlayerState> {
     let mut ret: Vec<&PlayerState> = players.iter().collect();
-    with_rng(|rng| ret.shuffle(rng));
+    ret.shuffle(rng);
     ret.into_iter()
 }
 
 #[allow(dead_code)]
-fn rand_player_iter_mut<'a>(players: &'a mut [PlayerState]) -> std::vec::IntoIter<&'a mut PlayerState> {
+fn rand_player_iter_mut<'a>(players: &'a mut [PlayerState], rng: &mut MatchRng) -> std::vec::IntoIter<&'a mut PlayerState> {
     let mut ret: Vec<&mut PlayerState> = players.iter_mut().collect();
-    with_rng(|rng| ret.shuffle(rng));
+    ret.shuffle(rng);
     ret.into_iter()
 }
 
-fn handle_player_collision(state: &mut GameState, conf: &GameConfig) {
+// separates overlapping players and pushes them out of the walls and penalty boxes. returns the
+// largest overlap left between two players if it gave up after collision_max_iterations
+fn handle_player_collision(state: &mut GameState, conf: &GameConfig, rng: &mut MatchRng) -> Option<f32> {
 
     let mut iterations = 0;
     let mut resolved = false;
@@ -40,9 +40,20 @@ fn handle_player_collision(state: &mut GameState, conf: &GameConfig) {
         }
     }
 
-    while !resolved && iterations < COLLISION_MAX_ITERATIONS {
+    let (tl, br) = (conf.field.top_left(), conf.field.bottom_right());
+    let blocked = |p: &PlayerState, correction: Vec2| {
+        let next = p.pos + correction;
+        next.x - p.radius < tl.x || next.x + p.radius > br.x || next.y - p.radius < tl.y || next.y + p.radius > br.y
+    };
+
+    while !resolved && iterations < conf.player.collision_max_iterations {
         resolved = true;
-        with_rng(|rng| pairs.shuffle(rng));
+        pairs.shuffle(rng);
+        let damping = if iterations >= COLLISION_DAMPING_START {
+            1.0 - conf.player.collision_damping
+        } else {
+            1.0
+        };
         // player on player collision
 
         for (i, j) in pairs.iter().copied() {
@@ -55,18 +66,25 @@ fn handle_player_collision(state: &mut GameState, conf: &GameConfig) {
                 resolved = false;
                 let dist = dist_sq.sqrt();
                 let dv = (p2.pos - p1.pos).normalize_or_else(|| {
-                    let angle = with_rng(|rng| rng.random_range(0.0..(2.0 * PI)));
+                    let angle = rng.random_range(0.0..(2.0 * PI));
                     Vec2::from_angle_rad(angle)
                 });
-                let diff = min_dist - dist;
-                let correction = (diff * 0.5 + EPSILON) * dv;
-                p1.pos -= correction;
-                p2.pos += correction;
+                let diff = (min_dist - dist) * damping;
+                // push a player wedged against a wall toward open space instead of into the wall
+                let (w1, w2) = if conf.player.collision_wall_bias {
+                    match (blocked(p1, -diff * dv), blocked(p2, diff * dv)) {
+                        (true, false) => (0.0, 1.0),
+                        (false, true) => (1.0, 0.0),
+                        _ => (0.5, 0.5),
+                    }
+                } else {
+                    (0.5, 0.5)
+                };
+                p1.pos -= (diff * w1 + EPSILON) * dv;
+                p2.pos += (diff * w2 + EPSILON) * dv;
             }
         }
 
-        let br = conf.field.bottom_right();
-
         let mut check_penalty_box = |p: &mut PlayerState, box_x_base: f32, x_multiplier: f32| {
             let box_width = conf.goal.penalty_box_width as f32;
             let box_radius = conf.goal.penalty_box_radius as f32;
@@ -119,28 +137,39 @@ fn handle_player_collision(state: &mut GameState, conf: &GameConfig) {
             }
         };
 
+        // under the dwell rule defenders may enter their own box, keepers always may
+        let dwell = conf.goal.penalty_box_rule == PenaltyBoxRule::Dwell;
         for p in state.players.iter_mut() {
-            if p.pos.x < conf.goal.penalty_box_width as f32 + p.radius {
-                check_penalty_box(p, 0.0, 1.0);
+            let defending = if p.id < NUM_PLAYERS { Team::A } else { Team::B };
+            let own_box_open = dwell || p.role == PlayerRole::Keeper;
+            if p.pos.x < tl.x + conf.goal.penalty_box_width as f32 + p.radius && !(own_box_open && defending == Team::A) {
+                check_penalty_box(p, tl.x, 1.0);
             }
 
-            if p.pos.x > br.x - conf.goal.penalty_box_width as f32 - p.radius {
+            if p.pos.x > br.x - conf.goal.penalty_box_width as f32 - p.radius && !(own_box_open && defending == Team::B) {
                 check_penalty_box(p, br.x, -1.0);
             }
         }
 
+        for p in state.players.iter_mut().filter(|p| p.role == PlayerRole::Keeper) {
+            let defending = if p.id < NUM_PLAYERS { Team::A } else { Team::B };
+            if confine_keeper(p, conf, defending) {
+                resolved = false;
+            }
+        }
+
         // player on wall collision
         for p in state.players.iter_mut() {
-            if p.pos.x - p.radius < 0.0 {
-                p.pos.x = p.radius + EPSILON;
+            if p.pos.x - p.radius < tl.x {
+                p.pos.x = tl.x + p.radius + EPSILON;
                 resolved = false;
             }
             if p.pos.x + p.radius > br.x {
                 p.pos.x = br.x - p.radius - EPSILON;
                 resolved = false;
             }
-            if p.pos.y - p.radius < 0.0 {
-                p.pos.y = p.radius + EPSILON;
+            if p.pos.y - p.radius < tl.y {
+                p.pos.y = tl.y + p.radius + EPSILON;
                 resolved = false;
             }
             if p.pos.y + p.radius > br.y {
@@ -151,42 +180,229 @@ fn handle_player_collision(state: &mut GameState, conf: &GameConfig) {
 
         iterations += 1;
     }
+
+    if resolved {
+        return None;
+    }
+    // the last pass may only have nudged players off walls or boxes, leaving no real overlap
+    let overlap = pairs.iter().fold(0.0f32, |max, &(i, j)| {
+        let (p1, p2) = (&state.players[i as usize], &state.players[j as usize]);
+        max.max(p1.radius + p2.radius - p1.pos.dist(&p2.pos))
+    });
+    (overlap > EPSILON).then_some(overlap)
 }
 
-fn closer(a: &Vec2, b: &Vec2, c: &Vec2) -> std::cmp::Ordering {
-    use std::cmp::Ordering;
-    let dist_ac = a.dist_sq(c);
-    let dist_bc = b.dist_sq(c);
-    let eps_sq = EPSILON.powi(2);
-    if dist_ac <= eps_sq && dist_bc <= eps_sq {
-        return if rand::rng().random_bool(0.5) {
-            Ordering::Less
-        } else {
-            Ordering::Greater
+// flags the passer's teammates standing beyond the opponent closest to its own goal
+fn flag_offside(state: &mut GameState, conf: &GameConfig, team: Team, passer: u32) {
+    state.offside = [false; NUM_PLAYERS as usize * 2];
+    if !conf.offside {
+        return;
+    }
+    // distance toward the goal the team attacks
+    let depth = |pos: &Vec2| match team {
+        Team::A => pos.x,
+        Team::B => -pos.x,
+    };
+    let last_defender = state.players[team.other()]
+        .iter()
+        .map(|p| depth(&p.pos))
+        .fold(f32::MIN, f32::max);
+    for p in state.players[team].iter().filter(|p| p.id != passer) {
+        if depth(&p.pos) > last_defender {
+            state.offside[p.id as usize] = true;
+        }
+    }
+}
+
+// pulls a keeper back inside its own penalty box, rounded corners included. returns whether it moved
+fn confine_keeper(p: &mut PlayerState, conf: &GameConfig, team: Team) -> bool {
+    let box_width = conf.goal.penalty_box_width as f32;
+    let box_radius = conf.goal.penalty_box_radius as f32;
+    let half_height = conf.goal.penalty_box_height as f32 * 0.5;
+    let center_y = conf.field.center().y;
+    let (base, x_multiplier) = match team {
+        Team::A => (conf.field.top_left().x, 1.0),
+        Team::B => (conf.field.bottom_right().x, -1.0),
+    };
+
+    // depth measured from the goal line into the field
+    let max_depth = (box_width - p.radius).max(0.0);
+    let max_off = (half_height - p.radius).max(0.0);
+    let mut depth = ((p.pos.x - base) * x_multiplier).clamp(0.0, max_depth);
+    let mut off_y = (p.pos.y - center_y).clamp(-max_off, max_off);
+
+    let (corner_depth, corner_off) = (box_width - box_radius, half_height - box_radius);
+    if depth > corner_depth && off_y.abs() > corner_off {
+        let to_corner = Vec2::new(depth - corner_depth, off_y.abs() - corner_off);
+        let limit = (box_radius - p.radius).max(0.0);
+        if to_corner.norm() > limit {
+            let clamped = to_corner.normalize_or_zero() * limit;
+            depth = corner_depth + clamped.x;
+            off_y = off_y.signum() * (corner_off + clamped.y);
+        }
+    }
+
+    let confined = Vec2::new(base + x_multiplier * depth, center_y + off_y);
+    if confined.dist_sq(&p.pos) <= EPSILON.powi(2) {
+        return false;
+    }
+    p.pos = confined;
+    true
+}
+
+// players lunge along their tackle direction. reaching the carrier wins the ball with a chance
+// that grows the closer the tackler ends up to the ball
+fn handle_tackles(
+    state: &mut GameState,
+    conf: &GameConfig,
+    actions: &PlayerArray<PlayerAction>,
+    rng: &mut MatchRng,
+    events: &mut Vec<RefereeEvent>,
+) {
+    for player in state.players.iter_mut() {
+        player.tackle_cooldown = player.tackle_cooldown.saturating_sub(1);
+    }
+    if conf.player.tackle_range <= 0.0 {
+        return;
+    }
+
+    let mut order: Vec<usize> = (0..state.players.len()).collect();
+    order.shuffle(rng);
+    for i in order {
+        let StateOption::Some(tackle) = actions[i].tackle else {
+            continue;
+        };
+        if state.players[i].tackle_cooldown > 0 || tackle.norm() == 0.0 {
+            continue;
+        }
+        let team = state.player_team(state.players[i].id).unwrap();
+        let tackler = &mut state.players[i];
+        tackler.tackle_cooldown = conf.player.tackle_cooldown;
+        tackler.pos += tackle.normalize_or_zero() * tackle.norm().min(1.0) * conf.player.tackle_range;
+
+        let BallPossessionState::Possessed { owner, team: owner_team, .. } = state.ball_possession else {
+            continue;
+        };
+        let (tackler, carrier) = (&state.players[i], &state.players[owner as usize]);
+        if owner_team == team || tackler.pos.dist(&carrier.pos) > tackler.radius + carrier.radius {
+            continue;
+        }
+        let reach = tackler.radius + carrier.radius + state.ball.radius;
+        let chance = (1.0 - tackler.pos.dist(&state.ball.pos) / reach).clamp(0.0, 1.0);
+        if !rng.random_bool(chance as f64) {
+            continue;
+        }
+
+        events.push(RefereeEvent::Tackle { tackler: tackler.id, carrier: owner });
+        state.recapture_cooldown[owner as usize] = conf.player.recapture_cooldown;
+        state.ball_possession = BallPossessionState::Possessed {
+            owner: tackler.id,
+            team,
+            capture_ticks: 0,
+            touches: 0,
         };
     }
-    dist_ac.total_cmp(&dist_bc)
 }
 
-fn closer_pickup(a: &PlayerState, b: &PlayerState, c: &Vec2) -> std::cmp::Ordering {
-    use std::cmp::Ordering;
+// an opponent running into the carrier fast enough knocks the ball loose
+fn handle_hard_collision(state: &mut GameState, conf: &GameConfig, events: &mut Vec<RefereeEvent>) {
+    if conf.player.knockout_speed <= 0.0 {
+        return;
+    }
+    let Some(owner) = state.ball_owner() else {
+        return;
+    };
+    let carrier = &state.players[owner as usize];
+    let team = state.player_team(owner).unwrap();
+    let carrier_vel = carrier.dir * carrier.speed;
+
+    let hit = state.players[team.other()].iter().find(|opponent| {
+        let normal = carrier.pos - opponent.pos;
+        if normal.norm_sq() >= (carrier.radius + opponent.radius).powi(2) {
+            return false;
+        }
+        let closing_speed = (opponent.dir * opponent.speed - carrier_vel).dot(normal.normalize_or_zero());
+        closing_speed >= conf.player.knockout_speed
+    });
+
+    if let Some(opponent) = hit {
+        events.push(RefereeEvent::Knockout { player: opponent.id, carrier: owner });
+        state.ball.pos = (carrier.pos + opponent.pos) * 0.5;
+        state.ball.vel = Vec2::ZERO;
+        state.ball_possession = BallPossessionState::Free;
+    }
+}
+
+// players whose reach falls short of c by the same distance tie. ties go to the favored team, then
+// the lower id, so the order is total and a coin flip for favored keeps contested balls side-neutral
+fn closer_pickup(a: &PlayerState, b: &PlayerState, c: &Vec2, favored: Team) -> std::cmp::Ordering {
     let pickup_ac = a.pos.dist(c) - a.pickup_radius;
     let pickup_bc = b.pos.dist(c) - b.pickup_radius;
-    if pickup_ac <= 1.0 && pickup_bc <= 1.0 {
-        return if with_rng(|rng| rng.random_bool(0.5)) {
-            Ordering::Less
-        } else {
-            Ordering::Greater
-        };
+    let tie_rank = |mut id: u32| {
+        if favored == Team::B {
+            mirror_player_id(&mut id);
+        }
+        id
+    };
+    if (pickup_ac - pickup_bc).abs() <= EPSILON {
+        return tie_rank(a.id).cmp(&tie_rank(b.id));
     }
     pickup_ac.total_cmp(&pickup_bc)
 }
 
+// a loose ball or opponent's kick in front of a goal goes to the defending keeper if it can reach it, else the
+// closest defender that can, so goal mouth scrambles end at once
+fn goal_mouth_claim(state: &GameState, conf: &GameConfig) -> Option<(u32, Team)> {
+    if conf.goal.goal_mouth_depth == 0 {
+        return None;
+    }
+    let team = [Team::A, Team::B]
+        .into_iter()
+        .find(|team| conf.in_goal_mouth(*team, &state.ball.pos, state.tick))?;
+    let loose = match state.ball_possession {
+        BallPossessionState::Free => true,
+        // kicks get a tick to leave the mouth, and the defenders' own passes aren't loose
+        BallPossessionState::Passing { team: kicker, launch_tick, .. } => kicker != team && launch_tick != state.tick,
+        BallPossessionState::Possessed { .. } => false,
+    };
+    if !loose {
+        return None;
+    }
+    state.players[team]
+        .iter()
+        .filter(|p| state.recapture_cooldown[p.id as usize] == 0)
+        .filter(|p| p.pos.dist_sq(&state.ball.pos) <= p.pickup_radius.powi(2))
+        .min_by(|a, b| {
+            (a.role != PlayerRole::Keeper)
+                .cmp(&(b.role != PlayerRole::Keeper))
+                .then_with(|| closer_pickup(a, b, &state.ball.pos, team))
+        })
+        .map(|p| (p.id, team))
+}
+
+// chance that an opponent within reach picks off a pass this tick. without pass_intercept_base
+// it always does, otherwise the chance grows the closer the ball's path runs to the opponent and
+// shrinks as the ball gets faster than a normal pass
+fn intercept_chance(p: &PlayerState, ball: &BallState, conf: &GameConfig, reach: f32) -> f32 {
+    if conf.player.pass_intercept_base <= 0.0 {
+        return 1.0;
+    }
+    let offset = p.pos - ball.pos;
+    let along = offset.dot(ball.vel.normalize_or_zero());
+    let miss = (offset.norm_sq() - along * along).max(0.0).sqrt();
+    let centered = (1.0 - miss / (p.pickup_radius * reach)).clamp(0.0, 1.0);
+    let speed = conf.player.pass_speed / ball.vel.norm().max(conf.player.pass_speed * EPSILON);
+    (conf.player.pass_intercept_base * centered * speed).clamp(0.0, 1.0)
+}
+
+// returns whether an offside was called
 fn handle_ball_state(
     state: &mut GameState,
     conf: &GameConfig,
     actions: &mut PlayerArray<PlayerAction>,
-) {
+    rng: &mut MatchRng,
+    events: &mut Vec<RefereeEvent>,
+) -> bool {
     use BallPossessionState::*;
     let mut resolved = false;
 
@@ -194,6 +410,7 @@ fn handle_ball_state(
         players,
         ball,
         ball_possession,
+        recapture_cooldown,
         ..
     } = state;
 
@@ -203,78 +420,165 @@ fn handle_ball_state(
         ..
     } = ball_possession
     {
-        let mut capturing = false;
-        for opponent in &players[team.other()] {
-            if ball.pos.dist_sq(&opponent.pos) <= opponent.pickup_radius.powi(2) {
-                *capture_ticks += 1;
-                capturing = true;
-            }
+        let capturers = players[team.other()]
+            .iter()
+            .filter(|opponent| recapture_cooldown[opponent.id as usize] == 0)
+            .filter(|opponent| ball.pos.dist_sq(&opponent.pos) <= opponent.pickup_radius.powi(2))
+            .count() as u32;
+        let capturing = capturers > 0 && capturers >= conf.ball.capturers_required;
+        if capturing {
+            *capture_ticks += capturers;
         }
         if !capturing && *capture_ticks > 0 {
             *capture_ticks -= 1;
         }
     }
 
+    // opponents who let a pass through this tick can't pick it up again as a loose ball
+    let mut missed: Vec<u32> = Vec::new();
     while !resolved {
         resolved = true;
+        if let Some((owner, team)) = goal_mouth_claim(state, conf) {
+            events.push(RefereeEvent::GoalMouthClaim { player: owner });
+            resolved = false;
+            state.ball_possession = Possessed { owner, team, capture_ticks: 0, touches: 0 };
+            continue;
+        }
         match &mut state.ball_possession {
             Possessed {
                 owner,
                 team,
                 capture_ticks,
+                ..
             } => {
-                if let StateOption::Some(pass) = actions[*owner as usize].pass {
+                // a pass wins over a shot requested on the same tick
+                let kick = match (actions[*owner as usize].pass, actions[*owner as usize].shoot) {
+                    (StateOption::Some(pass), _) => Some((pass, false)),
+                    (StateOption::None, StateOption::Some(shot)) => Some((shot, true)),
+                    _ => None,
+                };
+                if let Some((pass, shot)) = kick {
                     let owner = *owner;
                     resolved = false;
                     actions[owner as usize].pass = StateOption::None;
+                    actions[owner as usize].shoot = StateOption::None;
                     let owner = &state.players[owner as usize];
 
                     let norm = pass.norm();
                     if norm == 0.0 {
                         continue;
                     }
+                    if !shot && norm < conf.player.min_pass {
+                        events.push(RefereeEvent::SoftPass { player: owner.id });
+                        continue;
+                    }
                     let mut pass = pass / norm;
                     let norm = norm.clamp(EPSILON, 1.0);
-                    pass *= norm;
-                    // TODO port over colins pass logic
-                    let err = with_rng(|rng| rng.random_range(-conf.player.pass_error..conf.player.pass_error));
-                    pass.rotate_deg(err);
-                    state.ball_possession = Passing { team: *team };
-                    state.ball.vel = pass * conf.player.pass_speed;
+                    let vel = if shot {
+                        let err = rng.random_range(-conf.player.shot_error..conf.player.shot_error);
+                        let speed = conf.player.pass_speed + (conf.player.shot_speed - conf.player.pass_speed) * norm;
+                        pass.rotate_deg(err) * speed
+                    } else {
+                        pass *= norm;
+                        // TODO port over colins pass logic
+                        let err = rng.random_range(-conf.player.pass_error..conf.player.pass_error);
+                        pass.rotate_deg(err);
+                        pass * conf.player.pass_speed
+                    };
+                    let team = *team;
+                    let passer = owner.id;
+                    state.ball_possession = Passing { team, passer, launch_tick: state.tick, shot };
+                    state.ball.vel = vel / conf.ball.mass;
                     state.ball.pos = owner.pos + owner.dir.normalize_or_zero() * (owner.radius + state.ball.radius);
+                    state.pass_origin = StateOption::Some(state.ball.pos);
+                    flag_offside(state, conf, team, passer);
+                    if state.kickoff_pending {
+                        if shot || is_shot(state, conf, team) {
+                            events.push(RefereeEvent::KickoffViolation { team });
+                            turn_over(state, team.other(), rng);
+                        } else {
+                            state.kickoff_pending = false;
+                        }
+                    }
                 } else if *capture_ticks > conf.ball.capture_ticks {
-                    resolved = false;
                     // get closest opponent to the ball
-                    let closest_opponent = rand_player_iter(&state.players[team.other()])
-                        .min_by(|a, b| closer_pickup(&a, &b, &state.ball.pos))
-                        .unwrap()
-                        .id;
+                    let closest_opponent = rand_player_iter(&state.players[team.other()], rng)
+                        .filter(|p| state.recapture_cooldown[p.id as usize] == 0)
+                        .min_by(|a, b| closer_pickup(a, b, &state.ball.pos, team.other()));
+                    if let Some(closest_opponent) = closest_opponent {
+                        resolved = false;
+                        state.recapture_cooldown[*owner as usize] = conf.player.recapture_cooldown;
+                        state.ball_possession = Possessed {
+                            owner: closest_opponent.id,
+                            team: team.other(),
+                            capture_ticks: 0,
+                            touches: 0,
+                        };
+                    }
+                }
+            }
+            Passing { team, passer, launch_tick, shot } => {
+                // freshly launched passes escape the press before opponents can intercept
+                let protected = state.tick < *launch_tick + conf.ball.pass_protection_ticks;
+                // shots are harder to pick off than passes
+                let reach = if *shot { conf.player.shot_catch_factor } else { 1.0 };
+                let mut opponents: Vec<&PlayerState> = rand_player_iter(&state.players[team.other()], rng)
+                    .filter(|p| !protected && state.recapture_cooldown[p.id as usize] == 0)
+                    .collect();
+                opponents.sort_by(|a, b| closer_pickup(a, b, &state.ball.pos, team.other()));
+                // without pass_intercept_base only the closest opponent can intercept. with it, every
+                // opponent in reach gets a try, closest first, since the one ahead may miss
+                if conf.player.pass_intercept_base <= 0.0 {
+                    opponents.truncate(1);
+                }
+                let interceptor = opponents
+                    .into_iter()
+                    .filter(|p| p.pos.dist_sq(&state.ball.pos) <= (p.pickup_radius * reach).powi(2))
+                    .find(|p| {
+                        let chance = intercept_chance(p, &state.ball, conf, reach);
+                        let caught = chance >= 1.0 || rng.random_bool(chance as f64);
+                        if !caught {
+                            missed.push(p.id);
+                        }
+                        caught
+                    });
+                if let Some(interceptor) = interceptor {
+                    resolved = false;
+                    state.recapture_cooldown[*passer as usize] = conf.player.recapture_cooldown;
                     state.ball_possession = Possessed {
-                        owner: closest_opponent,
+                        owner: interceptor.id,
                         team: team.other(),
                         capture_ticks: 0,
+                        touches: 0,
                     };
+                    continue;
                 }
-            }
-            Passing { team } => {
-                let closest_opponent = rand_player_iter(&state.players[team.other()])
-                    .min_by(|a, b| closer_pickup(&a, &b, &state.ball.pos))
-                    .unwrap();
-                if closest_opponent.pos.dist_sq(&state.ball.pos)
-                    <= closest_opponent.pickup_radius.powi(2)
-                {
+                // any teammate in the pass lane can receive it, not just the closest
+                let receiver = rand_player_iter(&state.players[*team], rng)
+                    .filter(|p| state.recapture_cooldown[p.id as usize] == 0)
+                    .filter(|p| p.id != *passer && p.pos.dist_sq(&state.ball.pos) <= p.pickup_radius.powi(2))
+                    .min_by(|a, b| closer_pickup(a, b, &state.ball.pos, *team));
+                if let Some(receiver) = receiver {
                     resolved = false;
+                    if state.offside[receiver.id as usize] {
+                        events.push(RefereeEvent::Offside { player: receiver.id });
+                        state.free_ball = StateOption::Some(team.other());
+                        state.ball.vel = Vec2::ZERO;
+                        state.ball_possession = Free;
+                        return true;
+                    }
                     state.ball_possession = Possessed {
-                        owner: closest_opponent.id,
-                        team: team.other(),
+                        owner: receiver.id,
+                        team: *team,
                         capture_ticks: 0,
+                        touches: 0,
                     };
                     continue;
                 }
-                let closest_teammate = rand_player_iter(&state.players[*team])
-                    .min_by(|a, b| closer_pickup(&a, &b, &state.ball.pos))
+                let closest_teammate = rand_player_iter(&state.players[*team], rng)
+                    .min_by(|a, b| closer_pickup(a, b, &state.ball.pos, *team))
                     .unwrap();
-                if closest_teammate.pos.dist_sq(&state.ball.pos)
+                if !protected && closest_teammate.pos.dist_sq(&state.ball.pos)
                     > closest_teammate.pickup_radius.powi(2)
                 {
                     resolved = false;
@@ -282,27 +586,53 @@ fn handle_ball_state(
                 }
             }
             Free => {
-                let closest = rand_player_iter(&state.players)
-                    .min_by(|a, b| closer_pickup(&a, &b, &state.ball.pos))
-                    .unwrap();
-                if closest.pos.dist_sq(&state.ball.pos) <= closest.pickup_radius.powi(2) {
+                let favored = if rng.random_bool(0.5) { Team::A } else { Team::B };
+                let closest = rand_player_iter(&state.players, rng)
+                    .filter(|p| state.recapture_cooldown[p.id as usize] == 0 && !missed.contains(&p.id))
+                    .min_by(|a, b| closer_pickup(a, b, &state.ball.pos, favored));
+                if let Some(closest) = closest.filter(|p| {
+                    p.pos.dist_sq(&state.ball.pos) <= p.pickup_radius.powi(2)
+                }) {
                     resolved = false;
+                    if state.offside[closest.id as usize] {
+                        events.push(RefereeEvent::Offside { player: closest.id });
+                        state.free_ball = StateOption::Some(state.player_team(closest.id).unwrap().other());
+                        return true;
+                    }
                     state.ball_possession = Possessed {
                         owner: closest.id,
                         team: state.player_team(closest.id).unwrap(),
                         capture_ticks: 0,
+                        touches: 0,
                     }
                 }
             }
         }
     }
+    false
+}
+
+fn ball_touched(prev: &BallPossessionState, cur: &BallPossessionState) -> bool {
+    use BallPossessionState::*;
+    match (prev, cur) {
+        (Possessed { owner: a, .. }, Possessed { owner: b, .. }) => a != b,
+        (_, Possessed { .. }) => true,
+        (Passing { .. }, Passing { .. }) => false,
+        (_, Passing { .. }) => true,
+        _ => false
+    }
 }
 
 fn handle_ball_stagnation(
     state: &mut GameState,
     conf: &GameConfig,
+    touched: bool,
+    events: &mut Vec<RefereeEvent>,
 ) -> bool {
-    if state.ball.pos.dist_sq(&state.ball_stagnation.center) <= conf.ball.stagnation_radius.powi(2) {
+    if touched && conf.ball.stagnation_reset_on_touch {
+        state.ball_stagnation.center = state.ball.pos;
+        state.ball_stagnation.tick = 0;
+    } else if state.ball.pos.dist_sq(&state.ball_stagnation.center) <= conf.ball.stagnation_radius.powi(2) {
         state.ball_stagnation.tick += 1;
     } else {
         state.ball_stagnation.center = state.ball.pos;
@@ -310,42 +640,359 @@ fn handle_ball_stagnation(
     }
 
     if state.ball_stagnation.tick >= conf.ball.stagnation_ticks {
-        println!("# Ball stayed stagnant for too long! Resetting field...");
+        // start counting afresh so a caller that doesn't reset sees each stall once
+        state.ball_stagnation.center = state.ball.pos;
+        state.ball_stagnation.tick = 0;
+        events.push(RefereeEvent::StagnationReset);
         return true;
     }
     false
 }
 
+fn handle_pass_loop(
+    state: &mut GameState,
+    conf: &GameConfig,
+    events: &mut Vec<RefereeEvent>,
+) -> bool {
+    let Some(owner) = state.ball_owner() else {
+        return false;
+    };
+    let team = state.player_team(owner).unwrap();
+    let prev_same_team = state.pass_loop.owners[0]
+        .option()
+        .is_some_and(|prev| state.player_team(prev) == Some(team));
+    let pass_loop = &mut state.pass_loop;
+
+    if pass_loop.owners[0] != StateOption::Some(owner) {
+        if prev_same_team && pass_loop.owners[1] == StateOption::Some(owner) {
+            pass_loop.count += 1;
+        } else {
+            pass_loop.count = 0;
+        }
+        pass_loop.owners = [StateOption::Some(owner), pass_loop.owners[0]];
+    }
+
+    let attacking_third = match team {
+        Team::A => 2,
+        Team::B => 0,
+    };
+    if conf.field.third(state.ball.pos.x) == attacking_third {
+        pass_loop.count = 0;
+    }
+
+    if conf.ball.pass_loop_limit > 0 && pass_loop.count >= conf.ball.pass_loop_limit {
+        events.push(RefereeEvent::PassLoopReset);
+        return true;
+    }
+    false
+}
+
+// forfeits the ball once a team strings too many passes together without progressing
+fn handle_pass_limit(
+    state: &mut GameState,
+    conf: &GameConfig,
+    prev: &BallPossessionState,
+    rng: &mut MatchRng,
+    events: &mut Vec<RefereeEvent>,
+) {
+    use BallPossessionState::*;
+    if conf.ball.pass_limit == 0 {
+        return;
+    }
+    let team = match state.ball_possession {
+        Possessed { team, .. } | Passing { team, .. } => team,
+        Free => return,
+    };
+    let streak = &mut state.pass_streak;
+    if streak.team != StateOption::Some(team) {
+        *streak = PassStreakState { team: StateOption::Some(team), count: 0 };
+    }
+
+    let launched = match (prev, &state.ball_possession) {
+        (Passing { passer: a, .. }, Passing { passer: b, .. }) => a != b,
+        (_, Passing { .. }) => true,
+        _ => false,
+    };
+    let shot = matches!(state.ball_possession, Passing { shot: true, .. }) || is_shot(state, conf, team);
+    let attacking_third = match team {
+        Team::A => 2,
+        Team::B => 0,
+    };
+    if (launched && shot) || conf.field.third(state.ball.pos.x) == attacking_third {
+        state.pass_streak.count = 0;
+        return;
+    }
+    if launched {
+        state.pass_streak.count += 1;
+    }
+    if state.pass_streak.count <= conf.ball.pass_limit {
+        return;
+    }
+
+    events.push(RefereeEvent::PassLimit { team });
+    state.pass_streak = PassStreakState { team: StateOption::Some(team.other()), count: 0 };
+    call_turnover(state, conf, team.other(), rng, events);
+}
+
+// a carrier that holds the ball for more than max_touches ticks without passing or shooting
+// loses it to the other team
+fn handle_touch_limit(state: &mut GameState, conf: &GameConfig, rng: &mut MatchRng, events: &mut Vec<RefereeEvent>) {
+    if conf.ball.max_touches == 0 || state.kickoff_pending {
+        return;
+    }
+    let BallPossessionState::Possessed { owner, team, touches, .. } = &mut state.ball_possession else {
+        return;
+    };
+    *touches += 1;
+    if *touches <= conf.ball.max_touches {
+        return;
+    }
+    events.push(RefereeEvent::TouchLimit { player: *owner });
+    let team = team.other();
+    turn_over(state, team, rng);
+}
+
+fn handle_box_dwell(
+    state: &mut GameState,
+    conf: &GameConfig,
+    rng: &mut MatchRng,
+    events: &mut Vec<RefereeEvent>,
+) {
+    if conf.goal.penalty_box_rule != PenaltyBoxRule::Dwell {
+        return;
+    }
+    for i in 0..state.players.len() {
+        let player = &state.players[i];
+        let team = state.player_team(player.id).unwrap();
+        if player.role == PlayerRole::Keeper || !conf.in_own_penalty_box(team, &player.pos) {
+            state.box_dwell[i] = 0;
+            continue;
+        }
+        state.box_dwell[i] += 1;
+        if state.box_dwell[i] <= conf.goal.box_dwell_ticks {
+            continue;
+        }
+        // only the team with the ball can lose it. otherwise the penalty waits until the player
+        // leaves the box or its team wins the ball while it is still inside
+        let owner_team = state.ball_owner().and_then(|owner| state.player_team(owner));
+        if owner_team != Some(team) {
+            continue;
+        }
+
+        events.push(RefereeEvent::BoxDwell { player: player.id });
+        state.box_dwell[i] = 0;
+        call_turnover(state, conf, team.other(), rng, events);
+    }
+}
+
+// the kickoff taker may only pass, moving with the ball turns it over. the tick the taker picks
+// the ball up is exempt, its bot didn't know yet that it would have it
+fn handle_kickoff_dribble(
+    state: &mut GameState,
+    actions: &PlayerArray<PlayerAction>,
+    prev_owner: Option<u32>,
+    rng: &mut MatchRng,
+    events: &mut Vec<RefereeEvent>,
+) {
+    if !state.kickoff_pending {
+        return;
+    }
+    let Some(owner) = state.ball_owner().filter(|owner| prev_owner == Some(*owner)) else {
+        return;
+    };
+    if actions[owner as usize].dir.norm() <= EPSILON {
+        return;
+    }
+    let team = state.player_team(owner).unwrap();
+    events.push(RefereeEvent::KickoffViolation { team });
+    turn_over(state, team.other(), rng);
+}
+
+// hands the ball to the closest player of team
+fn turn_over(state: &mut GameState, team: Team, rng: &mut MatchRng) {
+    let closest = rand_player_iter(&state.players[team], rng)
+        .min_by(|a, b| closer_pickup(a, b, &state.ball.pos, team))
+        .unwrap()
+        .id;
+    state.ball.vel = Vec2::ZERO;
+    state.ball_possession = BallPossessionState::Possessed {
+        owner: closest,
+        team,
+        capture_ticks: 0,
+        touches: 0,
+    };
+}
+
+// turns the ball over to team, or lets them play on first when the advantage rule is on. a
+// turnover already pending wins over later ones
+fn call_turnover(
+    state: &mut GameState,
+    conf: &GameConfig,
+    team: Team,
+    rng: &mut MatchRng,
+    events: &mut Vec<RefereeEvent>,
+) {
+    if conf.advantage_ticks == 0 {
+        turn_over(state, team, rng);
+        return;
+    }
+    if state.pending_foul == StateOption::None {
+        events.push(RefereeEvent::AdvantagePlayed { team });
+        state.pending_foul = StateOption::Some(PendingFoul { team, ticks_left: conf.advantage_ticks });
+    }
+}
+
+// a shot by the favored team waives a pending turnover, otherwise it's enforced once the window
+// runs out, unless they already have the ball by then
+fn handle_advantage(state: &mut GameState, conf: &GameConfig, rng: &mut MatchRng, events: &mut Vec<RefereeEvent>) {
+    let StateOption::Some(PendingFoul { team, ticks_left }) = state.pending_foul else {
+        return;
+    };
+    let shot = matches!(
+        state.ball_possession,
+        BallPossessionState::Passing { team: kicker, launch_tick, shot, .. }
+            if kicker == team && launch_tick == state.tick && (shot || is_shot(state, conf, team))
+    );
+    if shot {
+        events.push(RefereeEvent::AdvantageWaived { team });
+        state.pending_foul = StateOption::None;
+        return;
+    }
+    if ticks_left > 1 {
+        state.pending_foul = StateOption::Some(PendingFoul { team, ticks_left: ticks_left - 1 });
+        return;
+    }
+    state.pending_foul = StateOption::None;
+    let owner_team = state.ball_owner().and_then(|owner| state.player_team(owner));
+    if owner_team != Some(team) {
+        events.push(RefereeEvent::AdvantageLost { team });
+        turn_over(state, team, rng);
+    }
+}
+
+// more than hoard.size teammates crowding the ball are slowed for the tick
+fn handle_hoarding(
+    state: &GameState,
+    conf: &GameConfig,
+    events: &mut Vec<RefereeEvent>,
+) -> PlayerArray<bool> {
+    let mut hoarding = [false; NUM_PLAYERS as usize * 2];
+    if conf.hoard.size == 0 {
+        return hoarding;
+    }
+    for team in [Team::A, Team::B] {
+        let crowd: Vec<u32> = state.players[team]
+            .iter()
+            .filter(|p| p.pos.dist_sq(&state.ball.pos) <= conf.hoard.radius.powi(2))
+            .map(|p| p.id)
+            .collect();
+        if crowd.len() as u32 > conf.hoard.size {
+            events.push(RefereeEvent::Hoarding { team, players: crowd.len() as u32 });
+            crowd.iter().for_each(|id| hoarding[*id as usize] = true);
+        }
+    }
+    hoarding
+}
+
+// defenders holding their position grow their pickup radius, rewarding anchoring over chasing
+fn handle_marking(
+    state: &mut GameState,
+    conf: &GameConfig,
+) {
+    if conf.player.marking_pickup_bonus <= 0.0 {
+        return;
+    }
+    let attacking = match state.ball_possession {
+        BallPossessionState::Possessed { team, .. } | BallPossessionState::Passing { team, .. } => Some(team),
+        BallPossessionState::Free => None,
+    };
+    for i in 0..state.players.len() {
+        let team = state.player_team(state.players[i].id).unwrap();
+        let player = &mut state.players[i];
+        if attacking != Some(team) && player.dir.norm() <= conf.player.marking_speed {
+            state.marking[i] = state.marking[i].saturating_add(1);
+        } else {
+            state.marking[i] = 0;
+        }
+        let base = conf.player.pickup_radius_of(player.role);
+        player.pickup_radius = if state.marking[i] >= conf.player.marking_ticks {
+            base + conf.player.marking_pickup_bonus
+        } else {
+            base
+        };
+    }
+}
+
+// bounces a loose ball off the round posts at the ends of both goal mouths
+fn handle_post_collision(state: &mut GameState, conf: &GameConfig) {
+    if !conf.goal.solid_posts {
+        return;
+    }
+    let h = conf.goal.current_height(conf, state.tick) as f32;
+    let post_radius = conf.goal.thickness as f32;
+    let [_, _, rest_left, rest_right] = conf.ball.restitution;
+    let (goal_a, goal_b) = (conf.field.goal_a(), conf.field.goal_b());
+    for (goal, restitution) in [(goal_a, rest_left), (goal_b, rest_right)] {
+        for post in [goal - Vec2::new(0.0, h / 2.0), goal + Vec2::new(0.0, h / 2.0)] {
+            let min_dist = post_radius + state.ball.radius;
+            let offset = state.ball.pos - post;
+            if offset.dist_sq(&Vec2::ZERO) >= min_dist.powi(2) {
+                continue;
+            }
+            // a ball exactly on the post is sent back into the field
+            let normal = offset.normalize_or_else(|| Vec2::new((conf.field.center().x - post.x).signum(), 0.0));
+            state.ball.pos = post + normal * (min_dist + EPSILON);
+            let along = state.ball.vel.dot(normal);
+            if along < 0.0 {
+                state.ball.vel -= normal * (along * (1.0 + restitution));
+            }
+        }
+    }
+}
+
 fn handle_scoring(
     state: &mut GameState,
     conf: &GameConfig,
+    events: &mut Vec<RefereeEvent>,
 ) -> bool {
-    let center = Vec2::new(conf.field.width as f32 / 2.0, conf.field.height as f32 / 2.0);
+    let center = conf.field.center();
     let h = conf.goal.current_height(conf, state.tick) as f32;
     let goal_bounds = (center.y - h / 2.0)..(center.y + h / 2.0);
     if !goal_bounds.contains(&state.ball.pos.y) {
         return false;
     }
-    if state.ball.pos.x - state.ball.radius <= conf.goal.thickness as f32 {
-        state.score.b += 1;
-        println!("# Bot B scored! A: {} B: {}", state.score.a, state.score.b);
+    let points = |goal: Vec2| match state.pass_origin {
+        StateOption::Some(origin) if origin.dist(&goal) >= conf.goal.long_range_dist as f32 => conf.goal.long_range_points,
+        _ => 1,
+    };
+    let (goal_a, goal_b) = (conf.field.goal_a(), conf.field.goal_b());
+    if state.ball.pos.x - state.ball.radius <= goal_a.x + conf.goal.thickness as f32 {
+        state.score.b += points(goal_a);
+        events.push(RefereeEvent::Goal { team: Team::B, score: state.score });
         return true;
     }
-    if state.ball.pos.x + state.ball.radius >= (conf.field.width - conf.goal.thickness) as f32 {
-        state.score.a += 1;
-        println!("# Bot A scored! A: {} B: {}", state.score.a, state.score.b);
+    if state.ball.pos.x + state.ball.radius >= goal_b.x - conf.goal.thickness as f32 {
+        state.score.a += points(goal_b);
+        events.push(RefereeEvent::Goal { team: Team::A, score: state.score });
         return true;
     }
     false
 }
 
-pub fn eval_reset(
+/// resets the ball to the center, leaving players where they are
+pub fn eval_reset_ball(
     state: &mut GameState,
     conf: &GameConfig,
-    formation: &TeamPair<[Vec2; NUM_PLAYERS as usize]>,
 ) {
     let center = conf.field.center();
-    let team_bias = Vec2::new(20.0, 0.0) * (state.score.a as i32 - state.score.b as i32).signum() as f32;
+    // the ball starts on the side of the team it favors: the one trailing, or the one
+    // awarded a free ball
+    let side = match state.free_ball {
+        StateOption::Some(Team::A) => -1,
+        StateOption::Some(Team::B) => 1,
+        StateOption::None => (state.score.a as i32 - state.score.b as i32).signum(),
+    };
+    let team_bias = Vec2::new(20.0, 0.0) * side as f32;
 
     state.ball = BallState {
         pos: center + team_bias,
@@ -353,42 +1000,118 @@ pub fn eval_reset(
         radius: conf.ball.radius
     };
     state.ball_possession = BallPossessionState::Free;
+    state.kickoff_pending = conf.kickoff_pass;
+    // measured from where the ball actually starts, not the center it's biased away from
     state.ball_stagnation = BallStagnationState {
-        center,
+        center: state.ball.pos,
         tick: 0,
     };
+    state.pass_loop = PassLoopState::default();
+    state.pass_streak = PassStreakState::default();
+    state.pass_origin = StateOption::None;
+    state.recapture_cooldown = [0; NUM_PLAYERS as usize * 2];
+    state.box_dwell = [0; NUM_PLAYERS as usize * 2];
+    state.marking = [0; NUM_PLAYERS as usize * 2];
+    state.offside = [false; NUM_PLAYERS as usize * 2];
+    state.free_ball = StateOption::None;
+    state.pending_foul = StateOption::None;
+    for player in state.players.iter_mut() {
+        player.pickup_radius = conf.player.pickup_radius_of(player.role);
+    }
+}
 
-    let translations = [0.0, center.x];
-    for ((dx, team), formation) in translations
+pub fn eval_reset(
+    state: &mut GameState,
+    conf: &GameConfig,
+    formation: &TeamPair<[Vec2; NUM_PLAYERS as usize]>,
+) {
+    eval_reset_ball(state, conf);
+
+    let center = conf.field.center();
+    let (tl, br) = (conf.field.top_left(), conf.field.bottom_right());
+    // each team's own half, so formations work for any field size, plus the tolerance past the
+    // halfway line. the last value points back toward the team's own goal
+    let tolerance = conf.spawn_halfway_tolerance.max(0.0);
+    let halves = [
+        (tl.x, (center.x + tolerance).min(br.x), -1.0),
+        ((center.x - tolerance).max(tl.x), br.x, 1.0),
+    ];
+    // clamps pos into a half and pushes it out of the spawn circle through that half
+    let spawn_at = |pos: Vec2, (lo, hi, back): (f32, f32, f32)| {
+        let clamp = |pos: Vec2| Vec2::new(pos.x.clamp(lo, hi), pos.y.clamp(tl.y, br.y));
+        let pos = clamp(pos);
+        if pos.dist_sq(&center) >= conf.spawn_ball_dist.powi(2) {
+            return pos;
+        }
+        let mut out = (pos - center).normalize_or_else(|| Vec2::new(back, 0.0));
+        // a player past halfway is pushed back out through its own half, the clamp would
+        // stop it short inside the circle otherwise
+        if out.x * back < 0.0 {
+            out.x = -out.x;
+        }
+        // clamped again in case the field is smaller than the spawn distance
+        clamp(center + out * conf.spawn_ball_dist)
+    };
+    for ((half, team), formation) in halves
         .iter()
         .copied()
         .zip(state.teams_mut().iter_mut()) 
         .zip(formation.iter())
     {
         for (player, pos) in team.iter_mut().zip(formation) {
+            player.pos = spawn_at(*pos, half);
+            player.dir = Vec2::ZERO;
+            player.vel = Vec2::ZERO;
+            player.stamina = conf.stamina.max;
+            player.sprint_charge = conf.player.sprint_ticks as f32;
+            player.tackle_cooldown = 0;
+        }
+    }
 
-            let mut pos = Vec2::new(
-                pos.x.clamp(dx, dx + center.x), 
-                pos.y.clamp(0.0, conf.field.height as f32)
-            );
-
-            if pos.dist_sq(&center) < conf.spawn_ball_dist.powi(2) {
-                pos = center + (pos - center).normalize_or_else(|| {
-                    Vec2::new((dx - EPSILON).signum(), 0.0)
-                }) * conf.spawn_ball_dist;
+    // separate overlapping players: opponents back into their own halves past the halfway line and
+    // teammates push apart vertically, which leaves them on the same side of it. a moved player can
+    // land in the spawn circle or on a third player, so this repeats until nothing overlaps
+    let n = NUM_PLAYERS as usize * 2;
+    let half_of = |id: usize| halves[id / NUM_PLAYERS as usize];
+    for _ in 0..conf.player.collision_max_iterations {
+        let mut moved = false;
+        for i in 0..n {
+            for j in (i + 1)..n {
+                let (p, q) = (&state.players[i], &state.players[j]);
+                let min_dist = p.radius + q.radius;
+                if p.pos.dist_sq(&q.pos) >= min_dist.powi(2) {
+                    continue;
+                }
+                let (mut pi, mut pj) = (p.pos, q.pos);
+                if i / NUM_PLAYERS as usize != j / NUM_PLAYERS as usize {
+                    pi.x = pi.x.min(center.x - p.radius - EPSILON);
+                    pj.x = pj.x.max(center.x + q.radius + EPSILON);
+                } else {
+                    let d = pj - pi;
+                    let gap = (min_dist.powi(2) - d.x.powi(2)).sqrt() - d.y.abs();
+                    let push = Vec2::new(0.0, (gap * 0.5 + EPSILON).copysign(d.y));
+                    pi -= push;
+                    pj += push;
+                }
+                state.players[i].pos = spawn_at(pi, half_of(i));
+                state.players[j].pos = spawn_at(pj, half_of(j));
+                moved = true;
             }
-
-            player.pos = pos;
-            player.dir = Vec2::ZERO;
+        }
+        if !moved {
+            break;
         }
     }
 }
 
+/// advances the state by one tick, pushing any referee decisions onto events
 pub fn eval_tick(
     state: &mut GameState, 
     conf: &GameConfig, 
-    mut actions: PlayerArray<PlayerAction>
-) -> bool {
+    mut actions: PlayerArray<PlayerAction>,
+    rng: &mut MatchRng,
+    events: &mut Vec<RefereeEvent>,
+) -> Option<ResetCause> {
 
     let is_endgame = state.tick >= conf.max_ticks;
 
@@ -396,21 +1119,77 @@ pub fn eval_tick(
     for action in &mut actions {
         let norm = action.dir.norm();
         action.dir = action.dir.normalize_or_zero() * norm.clamp(0.0, 1.0);
-        action.pass.option().map(|pass| pass.normalize_or_zero());
     }
 
-    handle_ball_state(state, conf, &mut actions);
+    state.recapture_cooldown.iter_mut().for_each(|c| *c = c.saturating_sub(1));
+
+    let prev_possession = state.ball_possession.clone();
+    handle_tackles(state, conf, &actions, rng, events);
+    let offside = handle_ball_state(state, conf, &mut actions, rng, events);
+    handle_pass_limit(state, conf, &prev_possession, rng, events);
+    handle_touch_limit(state, conf, rng, events);
+    let prev_owner = match prev_possession {
+        Possessed { owner, .. } => Some(owner),
+        _ => None,
+    };
+    handle_kickoff_dribble(state, &actions, prev_owner, rng, events);
+    let touched = ball_touched(&prev_possession, &state.ball_possession);
+    if state.ball_owner().is_some() {
+        state.pass_origin = StateOption::None;
+        state.offside = [false; NUM_PLAYERS as usize * 2];
+    }
 
+    let hoarding = handle_hoarding(state, conf, events);
     for (player, action) in state.players.iter_mut().zip(actions.iter()) {
         let speed_modifier = match state.ball_possession {
             Possessed { owner, .. } if owner == player.id => conf.player.possession_slowdown,
             _ => 1.0
         };
-        player.dir = action.dir * speed_modifier;
-        player.pos += player.dir * player.speed;
+        let hoard_modifier = if hoarding[player.id as usize] { conf.hoard.debuf } else { 1.0 };
+        let stamina_modifier = conf.stamina.speed_factor(player.stamina);
+        let sprinting = action.sprint && conf.player.sprint_ticks > 0 && player.sprint_charge >= 1.0;
+        let sprint_modifier = if sprinting {
+            player.sprint_charge -= 1.0;
+            // the carrier gets only part of the boost
+            let carrying = matches!(state.ball_possession, Possessed { owner, .. } if owner == player.id);
+            let boost = conf.player.sprint_factor - 1.0;
+            1.0 + if carrying { boost * conf.player.possession_slowdown } else { boost }
+        } else {
+            player.sprint_charge = (player.sprint_charge + conf.player.sprint_recharge)
+                .min(conf.player.sprint_ticks as f32);
+            1.0
+        };
+        let target = action.dir * speed_modifier * hoard_modifier * stamina_modifier * sprint_modifier;
+        // the requested direction is only a target, the velocity turns toward it
+        player.vel = if conf.player.max_accel > 0.0 {
+            player.vel.move_toward(target * player.speed, conf.player.max_accel)
+        } else {
+            target * player.speed
+        };
+        player.dir = if player.speed > 0.0 { player.vel / player.speed } else { target };
+        if conf.stamina.max > 0.0 {
+            let effort = action.dir.norm();
+            player.stamina = if effort <= conf.stamina.rest_speed {
+                player.stamina + conf.stamina.regen
+            } else {
+                player.stamina - conf.stamina.drain * effort
+            }
+            .clamp(0.0, conf.stamina.max);
+        }
+        player.pos += player.vel;
     }
+    handle_marking(state, conf);
 
-    handle_player_collision(state, conf);
+    handle_hard_collision(state, conf, events);
+    if let Some(overlap) = handle_player_collision(state, conf, rng) {
+        events.push(RefereeEvent::CollisionsUnresolved { iterations: conf.player.collision_max_iterations, overlap });
+    }
+    handle_box_dwell(state, conf, rng, events);
+    handle_advantage(state, conf, rng, events);
+    // a steal or turnover ends the kickoff as well as a pass, the next carrier plays freely
+    if prev_owner.is_some() && state.ball_owner() != prev_owner {
+        state.kickoff_pending = false;
+    }
 
     if let Possessed { owner, .. } = state.ball_possession {
         state.ball.vel = Vec2::ZERO;
@@ -420,43 +1199,1263 @@ pub fn eval_tick(
         let friction = if is_endgame {
             1.01
         } else {
-            conf.ball.friction
+            conf.ball.friction_at(&conf.field, &state.ball.pos)
         };
         state.ball.pos += state.ball.vel;
         state.ball.vel *= friction;
+        let (tl, br) = (conf.field.top_left(), conf.field.bottom_right());
         let (left, right, top, bottom) = (
-            state.ball.radius,
-            conf.field.width as f32 - state.ball.radius,
-            state.ball.radius,
-            conf.field.height as f32 - state.ball.radius,
+            tl.x + state.ball.radius,
+            br.x - state.ball.radius,
+            tl.y + state.ball.radius,
+            br.y - state.ball.radius,
         );
+        let [rest_top, rest_bottom, rest_left, rest_right] = conf.ball.restitution
+            .map(|r| if conf.ball.decouple_restitution { r } else { r * friction.powi(2) });
         if state.ball.pos.x < left {
             state.ball.pos.x = left + EPSILON;
-            state.ball.vel.x *= -friction.powi(2);
+            state.ball.vel.x *= -rest_left;
         }
         if state.ball.pos.x > right {
             state.ball.pos.x = right - EPSILON;
-            state.ball.vel.x *= -friction.powi(2);
+            state.ball.vel.x *= -rest_right;
         }
         if state.ball.pos.y < top {
             state.ball.pos.y = top + EPSILON;
-            state.ball.vel.y *= -friction.powi(2);
+            state.ball.vel.y *= -rest_top;
         }
         if state.ball.pos.y > bottom {
             state.ball.pos.y = bottom - EPSILON;
-            state.ball.vel.y *= -friction.powi(2);
+            state.ball.vel.y *= -rest_bottom;
         }
+        handle_post_collision(state, conf);
     }
 
     state.tick += 1;
 
-    if handle_scoring(state, conf) {
-        return true;
+    if handle_scoring(state, conf, events) {
+        return Some(ResetCause::Goal);
     }
 
-    if handle_ball_stagnation(state, conf) {
-        return true;
+    if offside {
+        return Some(ResetCause::Offside);
     }
 
-    false
+    if handle_ball_stagnation(state, conf, touched, events) {
+        return Some(ResetCause::Stagnation);
+    }
+
+    if handle_pass_loop(state, conf, events) {
+        return Some(ResetCause::PassLoop);
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // both teams spread across their own half, well clear of each other and the ball
+    fn kicked_off(conf: &GameConfig) -> GameState {
+        let mut state = GameState::new(conf);
+        let center = conf.field.center();
+        let a: [Vec2; NUM_PLAYERS as usize] =
+            std::array::from_fn(|i| Vec2::new(center.x - 200.0, 100.0 + 120.0 * i as f32));
+        let mut b = a;
+        b.iter_mut().for_each(|pos| mirror_pos(pos, conf));
+        eval_reset(&mut state, conf, &TeamPair::new(a, b));
+        state
+    }
+
+    fn give_ball(state: &mut GameState, owner: u32) {
+        let team = state.player_team(owner).unwrap();
+        state.ball.pos = state.players[owner as usize].pos;
+        state.ball_possession = BallPossessionState::Possessed { owner, team, capture_ticks: 0, touches: 0 };
+    }
+
+    fn tick(state: &mut GameState, conf: &GameConfig, actions: PlayerArray<PlayerAction>) {
+        eval_tick(state, conf, actions, &mut match_rng(1), &mut Vec::new());
+    }
+
+    fn kickoff_conf() -> GameConfig {
+        GameConfig { kickoff_pass: true, ..Default::default() }
+    }
+
+    // a free ball exactly halfway between players a and b, far from everyone else
+    fn contested(conf: &GameConfig, a: u32, b: u32) -> GameState {
+        let mut state = kicked_off(conf);
+        let ball = Vec2::new(conf.field.center().x, conf.field.height as f32 - 40.0);
+        let gap = conf.player.pickup_radius * 0.5;
+        state.players[a as usize].pos = ball - Vec2::new(gap, 0.0);
+        state.players[b as usize].pos = ball + Vec2::new(gap, 0.0);
+        state.ball.pos = ball;
+        state.ball.vel = Vec2::ZERO;
+        state.ball_possession = BallPossessionState::Free;
+        state
+    }
+
+    // team size is the compile-time NUM_PLAYERS, so an empty or one-player team never reaches
+    // the sim. the runtime equivalent is a team with no player able to take the ball
+    #[test]
+    fn no_eligible_player_leaves_the_ball_alone() {
+        let conf = GameConfig::default();
+        let mut state = contested(&conf, 0, NUM_PLAYERS);
+        state.recapture_cooldown = [10; NUM_PLAYERS as usize * 2];
+        tick(&mut state, &conf, Default::default());
+        assert!(state.ball_possession == BallPossessionState::Free);
+
+        state.ball_possession = BallPossessionState::Passing { team: Team::A, passer: 0, launch_tick: 0, shot: false };
+        tick(&mut state, &conf, Default::default());
+        assert_eq!(state.ball_owner(), None);
+    }
+
+    #[test]
+    fn equidistant_pickup_is_side_neutral() {
+        let conf = GameConfig::default();
+        let winner = |a: u32, b: u32, seed: u64| {
+            let mut state = contested(&conf, a, b);
+            eval_tick(&mut state, &conf, Default::default(), &mut match_rng(seed), &mut Vec::new());
+            state.ball_owner().unwrap()
+        };
+
+        let owners: Vec<u32> = (0..40).map(|seed| winner(0, NUM_PLAYERS, seed)).collect();
+        assert!(owners.iter().all(|owner| [0, NUM_PLAYERS].contains(owner)));
+        assert!(owners.contains(&0) && owners.contains(&NUM_PLAYERS), "{owners:?}");
+        // the same seed settles the same tie the same way
+        assert!((0..40).all(|seed| winner(0, NUM_PLAYERS, seed) == owners[seed as usize]));
+
+        // teammates tie by id
+        assert!((0..10).all(|seed| winner(2, 1, seed) == 1));
+    }
+
+    #[test]
+    fn slow_passes_are_intercepted_more_often() {
+        let conf = GameConfig {
+            player: PlayerConfig { pass_intercept_base: 0.6, ..Default::default() },
+            ..Default::default()
+        };
+        let intercepted = |speed: f32| {
+            (0..200).filter(|seed| {
+                let mut state = kicked_off(&conf);
+                let defender = Vec2::new(conf.field.center().x, 60.0);
+                state.players[NUM_PLAYERS as usize].pos = defender;
+                // the ball runs straight through the defender
+                state.ball.pos = defender;
+                state.ball.vel = Vec2::new(speed, 0.0);
+                state.ball_possession = BallPossessionState::Passing { team: Team::A, passer: 0, launch_tick: 0, shot: false };
+                eval_tick(&mut state, &conf, Default::default(), &mut match_rng(*seed), &mut Vec::new());
+                state.ball_owner() == Some(NUM_PLAYERS)
+            }).count()
+        };
+        let (slow, fast) = (intercepted(conf.player.pass_speed), intercepted(conf.player.pass_speed * 4.0));
+        assert!(slow > fast * 2 && fast > 0, "slow {slow}, fast {fast}");
+    }
+
+    #[test]
+    fn soft_pass_is_rejected() {
+        let conf = GameConfig {
+            player: PlayerConfig { min_pass: 0.5, ..Default::default() },
+            ..Default::default()
+        };
+        let passed = |strength: f32| {
+            let mut state = kicked_off(&conf);
+            give_ball(&mut state, 1);
+            let mut actions = PlayerArray::<PlayerAction>::default();
+            actions[1].pass = StateOption::Some(Vec2::new(0.0, strength));
+            let mut events = Vec::new();
+            eval_tick(&mut state, &conf, actions, &mut match_rng(1), &mut events);
+            let soft = events.contains(&RefereeEvent::SoftPass { player: 1 });
+            (state.ball_possession.clone(), soft)
+        };
+        let (possession, soft) = passed(0.2);
+        assert!(matches!(possession, BallPossessionState::Possessed { owner: 1, .. }) && soft);
+        let (possession, soft) = passed(0.8);
+        assert!(matches!(possession, BallPossessionState::Passing { passer: 1, .. }) && !soft);
+    }
+
+    #[test]
+    fn teammate_in_the_lane_receives_the_pass() {
+        let conf = GameConfig::default();
+        let mut state = kicked_off(&conf);
+        give_ball(&mut state, 0);
+        // player 1 stands between the passer and player 2, the pass's target
+        let mut actions = PlayerArray::<PlayerAction>::default();
+        actions[0].pass = StateOption::Some((state.players[2].pos - state.players[0].pos).normalize_or_zero());
+        tick(&mut state, &conf, actions);
+        for _ in 0..20 {
+            if state.ball_owner().is_some() {
+                break;
+            }
+            tick(&mut state, &conf, Default::default());
+        }
+        assert_eq!(state.ball_owner(), Some(1));
+    }
+
+    #[test]
+    fn fresh_pass_escapes_the_press() {
+        let mut conf = GameConfig::default();
+        conf.ball.pass_protection_ticks = 3;
+        let mut state = kicked_off(&conf);
+        let defender = Vec2::new(conf.field.center().x, 60.0);
+        state.players[NUM_PLAYERS as usize].pos = defender;
+        state.ball.pos = defender;
+        state.ball.vel = Vec2::new(0.5, 0.0);
+        state.ball_possession = BallPossessionState::Passing { team: Team::A, passer: 0, launch_tick: state.tick, shot: false };
+
+        let owners: Vec<_> = (0..4).map(|_| {
+            tick(&mut state, &conf, Default::default());
+            state.ball_owner()
+        }).collect();
+        assert_eq!(owners, [None, None, None, Some(NUM_PLAYERS)]);
+    }
+
+    #[test]
+    fn full_power_shot_outruns_a_pass() {
+        let conf = GameConfig::default();
+        // how far the ball travels on the tick after player 1 kicks it
+        let flight = |shot: bool| {
+            let mut state = kicked_off(&conf);
+            give_ball(&mut state, 1);
+            let mut actions = PlayerArray::<PlayerAction>::default();
+            let kick = StateOption::Some(Vec2::new(1.0, 0.0));
+            if shot {
+                actions[1].shoot = kick;
+            } else {
+                actions[1].pass = kick;
+            }
+            tick(&mut state, &conf, actions);
+            let before = state.ball.pos;
+            tick(&mut state, &conf, Default::default());
+            state.ball.pos.dist(&before)
+        };
+        let (pass, shot) = (flight(false), flight(true));
+        assert!((pass - conf.player.pass_speed * conf.ball.friction).abs() < 0.1, "pass moved {pass}");
+        assert!(shot > pass * 1.2, "shot moved {shot}, pass {pass}");
+    }
+
+    #[test]
+    fn pass_beyond_the_last_defender_is_offside() {
+        let conf = GameConfig { offside: true, ..Default::default() };
+        // player 1 passes along the top edge to player 2 standing at x, team B's defenders are all at
+        // the same depth
+        let pass_to = |x: f32| {
+            let mut state = kicked_off(&conf);
+            state.players[1].pos = Vec2::new(300.0, 40.0);
+            state.players[2].pos = Vec2::new(x, 40.0);
+            give_ball(&mut state, 1);
+            let mut actions = PlayerArray::<PlayerAction>::default();
+            actions[1].pass = StateOption::Some(Vec2::new(1.0, 0.0));
+            let mut events = Vec::new();
+            let mut reset = eval_tick(&mut state, &conf, actions, &mut match_rng(1), &mut events);
+            while reset.is_none() && state.ball_owner() != Some(2) {
+                assert!(state.tick < 100, "the pass never arrived");
+                reset = eval_tick(&mut state, &conf, Default::default(), &mut match_rng(1), &mut events);
+            }
+            (reset, events, state)
+        };
+        let last_defender = kicked_off(&conf).players[NUM_PLAYERS as usize].pos.x;
+
+        let (reset, events, state) = pass_to(last_defender - 100.0);
+        assert!(reset.is_none() && events.is_empty());
+        assert_eq!(state.ball_owner(), Some(2));
+
+        let (reset, events, state) = pass_to(last_defender + 100.0);
+        assert_eq!(reset, Some(ResetCause::Offside));
+        assert!(events.contains(&RefereeEvent::Offside { player: 2 }));
+        assert!(state.free_ball == StateOption::Some(Team::B));
+    }
+
+    #[test]
+    fn only_the_closest_opponent_can_catch_a_shot() {
+        let conf = GameConfig::default();
+        let mut state = kicked_off(&conf);
+        let ball = Vec2::new(conf.field.center().x, 60.0);
+        // a wide reach puts the first defender closest to the ball, but the shot still passes
+        // outside its shortened reach
+        let (wide, near) = (NUM_PLAYERS, NUM_PLAYERS + 1);
+        state.players[wide as usize].pickup_radius = 40.0;
+        state.players[wide as usize].pos = ball - Vec2::new(0.0, 25.0);
+        state.players[near as usize].pos = ball + Vec2::new(0.0, 12.0);
+        state.ball.pos = ball;
+        state.ball.vel = Vec2::new(1.0, 0.0);
+        state.ball_possession = BallPossessionState::Passing { team: Team::A, passer: 0, launch_tick: 0, shot: true };
+
+        tick(&mut state, &conf, Default::default());
+        // the shot isn't caught and the loose ball goes to the closest player
+        assert_eq!(state.ball_owner(), Some(wide));
+    }
+
+    #[test]
+    fn unsolvable_pileup_is_reported() {
+        let conf = GameConfig {
+            player: PlayerConfig { collision_max_iterations: 10, ..Default::default() },
+            ..Default::default()
+        };
+        let unresolved = |state: &mut GameState| {
+            let mut events = Vec::new();
+            eval_tick(state, &conf, Default::default(), &mut match_rng(1), &mut events);
+            events.into_iter().find_map(|event| match event {
+                RefereeEvent::CollisionsUnresolved { iterations, overlap } => Some((iterations, overlap)),
+                _ => None,
+            })
+        };
+
+        let mut state = kicked_off(&conf);
+        assert_eq!(unresolved(&mut state), None);
+
+        // players too wide for all of them to fit on the field at once
+        let mut state = kicked_off(&conf);
+        let radius = conf.field.height as f32 * 0.5;
+        state.players.iter_mut().for_each(|p| p.radius = radius);
+        let (iterations, overlap) = unresolved(&mut state).expect("the pileup was reported as solved");
+        assert_eq!(iterations, 10);
+        assert!(overlap > EPSILON, "{overlap}");
+    }
+
+    #[test]
+    fn wall_bias_frees_a_cornered_player_sooner() {
+        // the fewest iterations that separate a player pinned in the top left corner by a teammate
+        let iterations = |collision_wall_bias: bool| {
+            (1..100).find(|&collision_max_iterations| {
+                let conf = GameConfig {
+                    player: PlayerConfig { collision_wall_bias, collision_max_iterations, ..Default::default() },
+                    ..Default::default()
+                };
+                let mut state = kicked_off(&conf);
+                state.players[0].pos = Vec2::new(11.0, 11.0);
+                state.players[1].pos = Vec2::new(18.0, 18.0);
+                handle_player_collision(&mut state, &conf, &mut match_rng(1)).is_none()
+            }).unwrap()
+        };
+        let (biased, unbiased) = (iterations(true), iterations(false));
+        assert!(biased < unbiased, "biased {biased}, unbiased {unbiased}");
+    }
+
+    #[test]
+    fn damping_steadies_a_pileup() {
+        // how far players move between iterations once damping kicks in, with every player piled
+        // onto the same spot
+        let jitter = |collision_damping: f32| {
+            let positions: Vec<_> = (COLLISION_DAMPING_START..COLLISION_DAMPING_START + 10)
+                .map(|collision_max_iterations| {
+                    let conf = GameConfig {
+                        player: PlayerConfig { collision_damping, collision_max_iterations, ..Default::default() },
+                        ..Default::default()
+                    };
+                    let mut state = kicked_off(&conf);
+                    for (i, p) in state.players.iter_mut().enumerate() {
+                        p.pos = conf.field.center() + Vec2::from_angle_rad(i as f32) * 2.0;
+                    }
+                    handle_player_collision(&mut state, &conf, &mut match_rng(1));
+                    state.players.map(|p| p.pos)
+                })
+                .collect();
+            positions
+                .windows(2)
+                .flat_map(|w| w[0].iter().zip(&w[1]).map(|(a, b)| a.dist_sq(b)))
+                .sum::<f32>()
+        };
+        let (undamped, damped) = (jitter(0.0), jitter(0.5));
+        assert!(damped < undamped * 0.5, "damped {damped}, undamped {undamped}");
+    }
+
+    #[test]
+    fn kickoff_shot_is_turned_over() {
+        let conf = kickoff_conf();
+        let mut state = kicked_off(&conf);
+        give_ball(&mut state, 1);
+        let mut actions = PlayerArray::<PlayerAction>::default();
+        actions[1].shoot = StateOption::Some(Vec2::new(1.0, 0.0));
+        tick(&mut state, &conf, actions);
+        let owner = state.ball_owner().expect("the ball was not turned over");
+        assert_eq!(state.player_team(owner), Some(Team::B));
+        assert!(!state.kickoff_pending);
+    }
+
+    #[test]
+    fn wide_kickoff_shot_is_turned_over() {
+        let conf = kickoff_conf();
+        let mut state = kicked_off(&conf);
+        give_ball(&mut state, 1);
+        let mut actions = PlayerArray::<PlayerAction>::default();
+        actions[1].shoot = StateOption::Some(Vec2::new(0.0, 1.0));
+        tick(&mut state, &conf, actions);
+        let owner = state.ball_owner().expect("the ball was not turned over");
+        assert_eq!(state.player_team(owner), Some(Team::B));
+        assert!(!state.kickoff_pending);
+    }
+
+    #[test]
+    fn kickoff_pass_is_allowed() {
+        let conf = kickoff_conf();
+        let mut state = kicked_off(&conf);
+        give_ball(&mut state, 1);
+        let mut actions = PlayerArray::<PlayerAction>::default();
+        actions[1].pass = StateOption::Some(Vec2::new(0.0, 1.0));
+        tick(&mut state, &conf, actions);
+        assert!(matches!(state.ball_possession, BallPossessionState::Passing { team: Team::A, passer: 1, .. }));
+        assert!(!state.kickoff_pending);
+    }
+
+    #[test]
+    fn kickoff_dribble_is_turned_over() {
+        let conf = kickoff_conf();
+        let mut state = kicked_off(&conf);
+        give_ball(&mut state, 1);
+        let mut actions = PlayerArray::<PlayerAction>::default();
+        actions[1].dir = Vec2::new(1.0, 0.0);
+        tick(&mut state, &conf, actions);
+        let owner = state.ball_owner().expect("the ball was not turned over");
+        assert_eq!(state.player_team(owner), Some(Team::B));
+        assert!(!state.kickoff_pending);
+    }
+
+    #[test]
+    fn kickoff_pickup_tick_is_exempt() {
+        let conf = kickoff_conf();
+        let mut state = kicked_off(&conf);
+        state.ball.pos = state.players[1].pos + Vec2::new(15.0, 0.0);
+        let mut actions = PlayerArray::<PlayerAction>::default();
+        actions[1].dir = Vec2::new(1.0, 0.0);
+        tick(&mut state, &conf, actions);
+        assert_eq!(state.ball_owner(), Some(1));
+        assert!(state.kickoff_pending);
+    }
+
+    #[test]
+    fn steal_ends_the_kickoff() {
+        let conf = kickoff_conf();
+        let mut state = kicked_off(&conf);
+        give_ball(&mut state, 1);
+        let thief = NUM_PLAYERS + 1;
+        state.players[thief as usize].pos = state.players[1].pos + Vec2::new(15.0, 0.0);
+        if let BallPossessionState::Possessed { capture_ticks, .. } = &mut state.ball_possession {
+            *capture_ticks = conf.ball.capture_ticks;
+        }
+        tick(&mut state, &conf, Default::default());
+        assert_eq!(state.ball_owner(), Some(thief));
+        assert!(!state.kickoff_pending);
+
+        // the new carrier plays freely
+        let mut actions = PlayerArray::<PlayerAction>::default();
+        actions[thief as usize].dir = Vec2::new(-1.0, 0.0);
+        tick(&mut state, &conf, actions);
+        assert_eq!(state.ball_owner(), Some(thief));
+    }
+
+    #[test]
+    fn stealing_can_take_two_defenders() {
+        let mut conf = GameConfig::default();
+        conf.ball.capturers_required = 2;
+        let owner_after = |defenders: u32| {
+            let mut state = kicked_off(&conf);
+            give_ball(&mut state, 1);
+            let carrier = state.players[1].pos;
+            for (i, side) in [1.0, -1.0].into_iter().take(defenders as usize).enumerate() {
+                state.players[NUM_PLAYERS as usize + i].pos = carrier + Vec2::new(22.0 * side, 0.0);
+            }
+            for _ in 0..conf.ball.capture_ticks * 2 {
+                tick(&mut state, &conf, Default::default());
+            }
+            state.ball_owner()
+        };
+        assert_eq!(owner_after(1), Some(1));
+        assert!(owner_after(2).is_some_and(|owner| owner >= NUM_PLAYERS));
+    }
+
+    #[test]
+    fn parked_ball_stagnates_once_at_the_threshold() {
+        let conf = GameConfig::default();
+        let mut state = kicked_off(&conf);
+        assert_eq!(state.ball_stagnation.tick, 0);
+        let mut events = Vec::new();
+        let fired: Vec<_> = (1..=conf.ball.stagnation_ticks)
+            .filter(|_| handle_ball_stagnation(&mut state, &conf, false, &mut events))
+            .collect();
+        assert_eq!(fired, [conf.ball.stagnation_ticks]);
+        assert_eq!(events, [RefereeEvent::StagnationReset]);
+        assert_eq!(state.ball_stagnation.tick, 0);
+    }
+
+    #[test]
+    fn passes_within_the_stagnation_radius_keep_play_alive() {
+        let mut conf = GameConfig::default();
+        conf.ball.stagnation_ticks = 20;
+        // two teammates passing back and forth every 5 ticks without the ball leaving the radius
+        let stalled = |conf: &GameConfig| {
+            let mut state = kicked_off(conf);
+            let possession = |tick: u32| match (tick / 5) % 4 {
+                0 => BallPossessionState::Possessed { owner: 1, team: Team::A, capture_ticks: 0, touches: 0 },
+                2 => BallPossessionState::Possessed { owner: 2, team: Team::A, capture_ticks: 0, touches: 0 },
+                n => BallPossessionState::Passing { team: Team::A, passer: n / 2 + 1, launch_tick: 0, shot: false },
+            };
+            (1..200).any(|tick| {
+                let touched = ball_touched(&possession(tick - 1), &possession(tick));
+                handle_ball_stagnation(&mut state, conf, touched, &mut Vec::new())
+            })
+        };
+        assert!(stalled(&conf));
+        conf.ball.stagnation_reset_on_touch = true;
+        assert!(!stalled(&conf));
+    }
+
+    #[test]
+    fn pass_loop_between_two_players_is_broken() {
+        let mut conf = GameConfig::default();
+        conf.ball.pass_loop_limit = 4;
+        // the number of possessions, alternating between players 1 and 2, before the reset
+        let possessions_until_reset = |ball_x: f32| {
+            let mut state = kicked_off(&conf);
+            state.ball.pos = Vec2::new(ball_x, conf.field.center().y);
+            (0..20).find(|i| {
+                let owner = 1 + i % 2;
+                state.ball_possession = BallPossessionState::Possessed { owner, team: Team::A, capture_ticks: 0, touches: 0 };
+                handle_pass_loop(&mut state, &conf, &mut Vec::new())
+            })
+        };
+        assert_eq!(possessions_until_reset(conf.field.center().x), Some(5));
+        // passing around in the attacking third isn't stalling
+        assert_eq!(possessions_until_reset(conf.field.width as f32 - 100.0), None);
+    }
+
+    #[test]
+    fn scoring_is_symmetric_in_y() {
+        let conf = GameConfig::default();
+        let field = &conf.field;
+        assert!(field.top_left() == field.origin());
+        assert!(field.goal_a().x == field.top_left().x && field.goal_b().x == field.bottom_right().x);
+        assert!(field.goal_a().y == field.center().y && field.goal_b().y == field.center().y);
+
+        // whether a ball at the goal line at y scores, and for whom
+        let scores = |x: f32, y: f32| {
+            let mut state = kicked_off(&conf);
+            state.ball.pos = Vec2::new(x, y);
+            handle_scoring(&mut state, &conf, &mut Vec::new()).then_some(state.score)
+        };
+        let (left, right) = (field.goal_a().x + 1.0, field.goal_b().x - 1.0);
+        for y in [10.0, 200.0, 230.0, 290.0, 300.0, 370.0, 590.0] {
+            let flipped = field.height as f32 - y;
+            assert!(scores(left, y) == scores(left, flipped), "y {y}");
+            assert!(scores(right, y) == scores(right, flipped), "y {y}");
+        }
+        assert!(scores(left, 300.0) == Some(TeamPair::new(0, 1)));
+        assert!(scores(right, 300.0) == Some(TeamPair::new(1, 0)));
+        assert!(scores(left, 10.0).is_none());
+    }
+
+    #[test]
+    fn post_deflects_a_ball_that_a_clean_shot_beats() {
+        let mut conf = GameConfig::default();
+        conf.goal.solid_posts = true;
+        let goal = conf.field.goal_b();
+        let post = goal - Vec2::new(0.0, conf.goal.current_height(&conf, 0) as f32 / 2.0);
+        // a free ball rolling straight at the goal line level with y, and whether it went in
+        let roll = |y: f32| {
+            let mut state = kicked_off(&conf);
+            state.ball_possession = BallPossessionState::Free;
+            state.ball.pos = Vec2::new(goal.x - 50.0, y);
+            state.ball.vel = Vec2::new(6.0, 0.0);
+            for _ in 0..30 {
+                if eval_tick(&mut state, &conf, Default::default(), &mut match_rng(1), &mut Vec::new()) == Some(ResetCause::Goal) {
+                    return (true, state);
+                }
+            }
+            (false, state)
+        };
+
+        let (scored, state) = roll(post.y);
+        assert!(!scored);
+        assert!(state.ball.vel.x < 0.0 && state.ball.pos.x < goal.x);
+        // one grazing the post from just outside the mouth is turned back too
+        let (scored, state) = roll(post.y - conf.goal.thickness as f32 * 0.5);
+        assert!(!scored && state.ball.vel.x < 0.0);
+
+        assert!(roll(goal.y).0);
+    }
+
+    #[test]
+    fn keeper_claims_a_loose_ball_in_the_goal_mouth() {
+        let mut conf = GameConfig::default();
+        conf.player.goalkeepers = true;
+        conf.goal.goal_mouth_depth = 120;
+        let attacker = NUM_PLAYERS as usize;
+        // a loose ball just outside team a's box, the attacker nearer to it than the keeper
+        let scrum = |conf: &GameConfig| {
+            let mut state = kicked_off(conf);
+            state.ball_possession = BallPossessionState::Free;
+            state.ball.pos = Vec2::new(100.0, 300.0);
+            state.ball.vel = Vec2::ZERO;
+            state.players[0].pos = Vec2::new(80.0, 300.0);
+            state.players[attacker].pos = Vec2::new(112.0, 300.0);
+            state
+        };
+        let mut actions = PlayerArray::<PlayerAction>::default();
+        actions[attacker].dir = Vec2::new(-1.0, 0.0);
+
+        let mut state = scrum(&conf);
+        for _ in 0..10 {
+            tick(&mut state, &conf, actions.clone());
+            assert_eq!(state.ball_owner(), Some(0));
+        }
+
+        let mut open = conf.clone();
+        open.goal.goal_mouth_depth = 0;
+        let mut state = scrum(&open);
+        tick(&mut state, &open, actions);
+        assert_eq!(state.ball_owner(), Some(attacker as u32));
+    }
+
+    #[test]
+    fn goal_is_one_event_for_the_scorer() {
+        let conf = GameConfig::default();
+        let mut state = kicked_off(&conf);
+        state.ball_possession = BallPossessionState::Free;
+        state.ball.pos = conf.field.goal_a() + Vec2::new(40.0, 0.0);
+        state.ball.vel = Vec2::new(-6.0, 0.0);
+        let mut events = Vec::new();
+        let reset = (0..30).find_map(|_| eval_tick(&mut state, &conf, Default::default(), &mut match_rng(1), &mut events));
+        assert_eq!(reset, Some(ResetCause::Goal));
+        let goals: Vec<_> = events.iter().filter(|event| matches!(event, RefereeEvent::Goal { .. })).collect();
+        assert_eq!(goals, [&RefereeEvent::Goal { team: Team::B, score: TeamPair::new(0, 1) }]);
+    }
+
+    #[test]
+    fn goal_config_sets_the_scoring_mouth() {
+        let conf = GameConfig {
+            goal: GoalConfig { normal_height: 200, thickness: 10, ..Default::default() },
+            ..Default::default()
+        };
+        let (center, radius) = (conf.field.center(), conf.ball.radius);
+        let scores = |x: f32, y: f32| {
+            let mut state = kicked_off(&conf);
+            state.ball.pos = Vec2::new(x, y);
+            handle_scoring(&mut state, &conf, &mut Vec::new())
+        };
+        let line = conf.field.goal_b().x - 10.0 - radius;
+        assert!(scores(line, center.y + 95.0));
+        assert!(!scores(line, center.y + 105.0));
+        assert!(!scores(line - 1.0, center.y));
+    }
+
+    #[test]
+    fn long_range_goal_scores_the_bonus() {
+        let mut conf = GameConfig::default();
+        conf.goal.long_range_points = 2;
+        let goal = conf.field.goal_b();
+        let scored_from = |dist: f32| {
+            let mut state = kicked_off(&conf);
+            state.ball.pos = goal - Vec2::new(conf.goal.thickness as f32, 0.0);
+            state.pass_origin = StateOption::Some(goal - Vec2::new(dist, 0.0));
+            assert!(handle_scoring(&mut state, &conf, &mut Vec::new()));
+            state.score.a
+        };
+        let line = conf.goal.long_range_dist as f32;
+        assert_eq!(scored_from(line + 50.0), 2);
+        assert_eq!(scored_from(line - 50.0), 1);
+    }
+
+    #[test]
+    fn symmetric_spawns_past_halfway_are_separated() {
+        let conf = GameConfig { spawn_halfway_tolerance: 50.0, ..Default::default() };
+        let center = conf.field.center();
+        // both teams ask for the same spots just past the halfway line, clear of the ball
+        let a = [40.0, 90.0, 510.0, 560.0].map(|y| Vec2::new(center.x + 5.0, y));
+        let mut b = a;
+        b.iter_mut().for_each(|pos| mirror_pos(pos, &conf));
+        let mut state = GameState::new(&conf);
+        eval_reset(&mut state, &conf, &TeamPair::new(a, b));
+
+        for pa in &state.players[Team::A] {
+            for pb in &state.players[Team::B] {
+                assert!(pa.pos.dist(&pb.pos) >= pa.radius + pb.radius, "{} and {} overlap", pa.id, pb.id);
+            }
+            assert!(pa.pos.x < center.x);
+        }
+        assert!(state.players[Team::B].iter().all(|pb| pb.pos.x > center.x));
+
+        // a's striker only overlaps b's second player, but backing off it lands on b's first, and
+        // b's first backing off the striker lands on its own teammate
+        let a = [Vec2::new(center.x + 6.0, 40.0), Vec2::new(100.0, 300.0), Vec2::new(100.0, 400.0), Vec2::new(100.0, 500.0)];
+        let b = [Vec2::new(center.x - 15.0, 40.0), Vec2::new(center.x + 12.0, 40.0), Vec2::new(900.0, 300.0), Vec2::new(900.0, 400.0)];
+        let mut state = GameState::new(&conf);
+        eval_reset(&mut state, &conf, &TeamPair::new(a, b));
+        for (i, p) in state.players.iter().enumerate() {
+            assert!(p.pos.dist(&center) >= conf.spawn_ball_dist - EPSILON, "{i} in the spawn circle");
+            for q in &state.players[i + 1..] {
+                assert!(p.pos.dist(&q.pos) >= p.radius + q.radius, "{} and {} overlap", p.id, q.id);
+            }
+        }
+    }
+
+    #[test]
+    fn striker_spawns_across_halfway_within_the_tolerance() {
+        let conf = GameConfig { spawn_halfway_tolerance: 20.0, ..Default::default() };
+        let center = conf.field.center();
+        // where team a's first player spawns when asked for pos
+        let spawn = |conf: &GameConfig, pos: Vec2| {
+            let mut state = GameState::new(conf);
+            let mut formation = TeamPair::new([Vec2::new(100.0, 100.0); NUM_PLAYERS as usize], [Vec2::ZERO; NUM_PLAYERS as usize]);
+            formation.a[0] = pos;
+            formation.b.iter_mut().for_each(|pos| mirror_pos(pos, conf));
+            eval_reset(&mut state, conf, &formation);
+            state.players[0].pos
+        };
+        let striker = Vec2::new(center.x + 10.0, 50.0);
+        assert_eq!(spawn(&conf, striker), striker);
+        assert_eq!(spawn(&conf, Vec2::new(center.x + 50.0, 50.0)), Vec2::new(center.x + 20.0, 50.0));
+        assert_eq!(spawn(&GameConfig::default(), striker), Vec2::new(center.x, 50.0));
+
+        // the tolerance doesn't reach into the circle around the ball
+        assert!(spawn(&conf, center + Vec2::new(10.0, 0.0)).dist(&center) >= conf.spawn_ball_dist - 1e-3);
+    }
+
+    #[test]
+    fn ball_only_reset_leaves_players_moving() {
+        let conf = GameConfig { ball_only_reset_on_stall: true, ..Default::default() };
+        assert!(conf.ball_only_reset(ResetCause::Stagnation));
+        assert!(!conf.ball_only_reset(ResetCause::Goal));
+
+        let mut state = kicked_off(&conf);
+        let mut actions = PlayerArray::<PlayerAction>::default();
+        actions.iter_mut().for_each(|action| action.dir = Vec2::new(1.0, 1.0));
+        tick(&mut state, &conf, actions);
+        give_ball(&mut state, 1);
+        let players = state.players.clone();
+        eval_reset_ball(&mut state, &conf);
+        for (before, after) in players.iter().zip(&state.players) {
+            assert_eq!((before.pos, before.vel, before.dir), (after.pos, after.vel, after.dir));
+            assert!(after.vel != Vec2::ZERO);
+        }
+        assert!(state.ball_possession == BallPossessionState::Free);
+    }
+
+    #[test]
+    fn robbed_player_waits_out_the_cooldown() {
+        // whether the robbed player gets the ball back within 15 ticks of losing it
+        let stole_back = |recapture_cooldown: u32| {
+            let mut conf = GameConfig {
+                player: PlayerConfig { recapture_cooldown, ..Default::default() },
+                ..Default::default()
+            };
+            conf.ball.capture_ticks = 5;
+            let mut state = kicked_off(&conf);
+            give_ball(&mut state, 1);
+            let thief = NUM_PLAYERS + 1;
+            state.players[thief as usize].pos = state.players[1].pos + Vec2::new(22.0, 0.0);
+            while state.ball_owner() == Some(1) {
+                tick(&mut state, &conf, Default::default());
+            }
+            assert_eq!(state.ball_owner(), Some(thief));
+            (0..15).any(|_| {
+                tick(&mut state, &conf, Default::default());
+                state.ball_owner() == Some(1)
+            })
+        };
+        assert!(stole_back(0));
+        assert!(!stole_back(20));
+    }
+
+    #[test]
+    fn hard_collision_knocks_the_ball_loose() {
+        let conf = GameConfig {
+            player: PlayerConfig { knockout_speed: 3.0, ..Default::default() },
+            ..Default::default()
+        };
+        let rammed = |speed: f32| {
+            let mut state = kicked_off(&conf);
+            give_ball(&mut state, 1);
+            let carrier = state.players[1].pos;
+            let opponent = &mut state.players[NUM_PLAYERS as usize + 1];
+            opponent.pos = carrier + Vec2::new(15.0, 0.0);
+            opponent.dir = Vec2::new(-1.0, 0.0);
+            opponent.speed = speed;
+            let mut events = Vec::new();
+            handle_hard_collision(&mut state, &conf, &mut events);
+            state.ball_possession == BallPossessionState::Free
+                && events.contains(&RefereeEvent::Knockout { player: NUM_PLAYERS + 1, carrier: 1 })
+        };
+        assert!(rammed(4.0));
+        assert!(!rammed(1.0));
+    }
+
+    #[test]
+    fn stationary_defender_reaches_farther() {
+        let conf = GameConfig {
+            player: PlayerConfig { marking_pickup_bonus: 10.0, marking_ticks: 5, ..Default::default() },
+            ..Default::default()
+        };
+        // a free ball just out of the defender's normal reach, while it stands still or shuffles
+        // side to side without getting any closer
+        let caught = |shuffle: bool| {
+            let mut state = kicked_off(&conf);
+            let defender = NUM_PLAYERS as usize;
+            state.ball.pos = state.players[defender].pos - Vec2::new(0.0, 30.0);
+            (0..10).any(|i| {
+                let mut actions = PlayerArray::<PlayerAction>::default();
+                if shuffle {
+                    actions[defender].dir = Vec2::new(if i % 2 == 0 { 1.0 } else { -1.0 }, 0.0);
+                }
+                tick(&mut state, &conf, actions);
+                state.ball_owner() == Some(NUM_PLAYERS)
+            })
+        };
+        assert!(caught(false));
+        assert!(!caught(true));
+    }
+
+    // a loose ball bouncing straight up and down the middle of the field, clear of every player
+    fn bouncing(conf: &GameConfig) -> GameState {
+        let mut state = kicked_off(conf);
+        state.ball.pos = conf.field.center();
+        state.ball.vel = Vec2::new(0.0, -5.0);
+        state
+    }
+
+    #[test]
+    fn frictionless_ball_keeps_its_speed() {
+        let mut conf = GameConfig::default();
+        conf.ball.friction = 1.0;
+        conf.ball.decouple_restitution = true;
+        let mut state = bouncing(&conf);
+        // several bounces off the top and bottom walls
+        for _ in 0..400 {
+            tick(&mut state, &conf, Default::default());
+        }
+        assert!(state.ball_possession == BallPossessionState::Free);
+        assert!((state.ball.vel.norm() - 5.0).abs() < 1e-3, "speed {}", state.ball.vel.norm());
+    }
+
+    // the speed kept through the tick the ball bounces off the top wall
+    fn bounce_ratio(conf: &GameConfig) -> f32 {
+        let mut state = bouncing(conf);
+        loop {
+            let before = state.ball.vel;
+            tick(&mut state, conf, Default::default());
+            if state.ball.vel.y > 0.0 {
+                return state.ball.vel.norm() / before.norm();
+            }
+        }
+    }
+
+    #[test]
+    fn restitution_coupling() {
+        let mut conf = GameConfig::default();
+        conf.ball.restitution = [0.5; 4];
+        let friction = conf.ball.friction;
+        // by default the bounce also loses friction squared, on top of the tick's friction
+        assert!((bounce_ratio(&conf) - friction.powi(3) * 0.5).abs() < 1e-4);
+        conf.ball.decouple_restitution = true;
+        assert!((bounce_ratio(&conf) - friction * 0.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn walls_bounce_by_their_own_restitution() {
+        let mut conf = GameConfig::default();
+        conf.ball.restitution = [0.8, 1.0, 0.3, 1.0];
+        conf.ball.decouple_restitution = true;
+        let friction = conf.ball.friction;
+        assert!((bounce_ratio(&conf) - friction * 0.8).abs() < 1e-4);
+
+        // along the top of the field to the left wall, above the goal mouth
+        let mut state = kicked_off(&conf);
+        state.ball.pos = Vec2::new(conf.field.center().x, 20.0);
+        state.ball.vel = Vec2::new(-5.0, 0.0);
+        let ratio = loop {
+            let before = state.ball.vel;
+            tick(&mut state, &conf, Default::default());
+            if state.ball.vel.x > 0.0 {
+                break state.ball.vel.norm() / before.norm();
+            }
+        };
+        assert!((ratio - friction * 0.3).abs() < 1e-4, "{ratio}");
+    }
+
+    #[test]
+    fn ball_slows_faster_in_a_high_friction_zone() {
+        let mut conf = GameConfig::default();
+        conf.ball.zone_friction = [1.0, 1.0, 0.9];
+        let mut state = kicked_off(&conf);
+        state.ball.pos = Vec2::new(conf.field.center().x, 20.0);
+        state.ball.vel = Vec2::new(10.0, 0.0);
+        let (mut middle, mut right) = (Vec::new(), Vec::new());
+        while right.len() < 5 {
+            let (pos, speed) = (state.ball.pos, state.ball.vel.norm());
+            tick(&mut state, &conf, Default::default());
+            let kept = state.ball.vel.norm() / speed;
+            // friction is taken where the ball starts the tick
+            match conf.field.third(pos.x) {
+                1 => middle.push(kept),
+                _ => right.push(kept),
+            }
+        }
+        assert!(middle.iter().all(|kept| (kept - conf.ball.friction).abs() < 1e-4), "{middle:?}");
+        assert!(right.iter().all(|kept| (kept - conf.ball.friction * 0.9).abs() < 1e-4), "{right:?}");
+    }
+
+    #[test]
+    fn heavy_ball_is_slower_and_stops_sooner() {
+        // player 1 passes along the top edge, returns the first tick's travel and how many ticks
+        // the ball takes to slow to a crawl
+        let pass = |mass: f32| {
+            let mut conf = GameConfig::default();
+            conf.ball.mass = mass;
+            let mut state = kicked_off(&conf);
+            state.players[1].pos = Vec2::new(50.0, 40.0);
+            give_ball(&mut state, 1);
+            let mut actions = PlayerArray::<PlayerAction>::default();
+            actions[1].pass = StateOption::Some(Vec2::new(1.0, 0.0));
+            let start = state.ball.pos;
+            tick(&mut state, &conf, actions);
+            let first = state.ball.pos.dist(&start);
+            let ticks = (1..1000).find(|_| {
+                tick(&mut state, &conf, Default::default());
+                state.ball.vel.norm() < 1.0
+            });
+            (first, ticks.expect("the ball never slowed down"))
+        };
+        let (light, heavy) = (pass(1.0), pass(2.0));
+        assert!(heavy.0 < light.0 * 0.6, "heavy ball moved {} on the first tick, light {}", heavy.0, light.0);
+        assert!(heavy.1 < light.1, "heavy ball slowed after {} ticks, light {}", heavy.1, light.1);
+    }
+
+    #[test]
+    fn frictionless_match_terminates() {
+        let mut conf = GameConfig { max_ticks: 300, endgame_ticks: 0, ..Default::default() };
+        conf.ball.friction = 1.0;
+        let mut state = bouncing(&conf);
+        let mut rng = match_rng(1);
+        let mut ticks = 0;
+        while !crate::game::sim::match_over(&state, &conf) {
+            assert!(ticks <= conf.max_ticks, "the match ran past max_ticks");
+            if eval_tick(&mut state, &conf, Default::default(), &mut rng, &mut Vec::new()).is_some() {
+                eval_reset_ball(&mut state, &conf);
+            }
+            ticks += 1;
+        }
+    }
+
+    fn dwell_conf() -> GameConfig {
+        let mut conf = GameConfig::default();
+        conf.goal.penalty_box_rule = PenaltyBoxRule::Dwell;
+        conf.goal.box_dwell_ticks = 3;
+        conf
+    }
+
+    // team A's player 1 parked in its own box
+    fn loitering(conf: &GameConfig) -> GameState {
+        let mut state = kicked_off(conf);
+        assert!(state.players[1].role != PlayerRole::Keeper);
+        state.players[1].pos = Vec2::new(conf.field.top_left().x + 20.0, conf.field.center().y);
+        state
+    }
+
+    fn owner_team(state: &GameState) -> Option<Team> {
+        state.ball_owner().and_then(|owner| state.player_team(owner))
+    }
+
+    #[test]
+    fn box_dwell_turns_the_ball_over() {
+        let conf = dwell_conf();
+        let mut state = loitering(&conf);
+        give_ball(&mut state, 2);
+        for _ in 0..conf.goal.box_dwell_ticks {
+            tick(&mut state, &conf, Default::default());
+        }
+        assert_eq!(owner_team(&state), Some(Team::A));
+        tick(&mut state, &conf, Default::default());
+        assert_eq!(owner_team(&state), Some(Team::B));
+    }
+
+    #[test]
+    fn box_dwell_waits_for_the_ball() {
+        let conf = dwell_conf();
+        let mut state = loitering(&conf);
+        give_ball(&mut state, NUM_PLAYERS + 2);
+        for _ in 0..conf.goal.box_dwell_ticks * 2 {
+            tick(&mut state, &conf, Default::default());
+        }
+        // the attackers keep the ball rather than being handed a turnover of their own ball
+        assert_eq!(state.ball_owner(), Some(NUM_PLAYERS + 2));
+        assert!(state.box_dwell[1] > conf.goal.box_dwell_ticks);
+
+        give_ball(&mut state, 2);
+        tick(&mut state, &conf, Default::default());
+        assert_eq!(owner_team(&state), Some(Team::B));
+    }
+
+    #[test]
+    fn hoarding_slows_the_crowd() {
+        let mut conf = GameConfig::default();
+        conf.hoard.size = 2;
+        let mut state = kicked_off(&conf);
+        // three of team A around a loose ball, player 3 well away from it
+        let ball = conf.field.center() + Vec2::new(-100.0, 0.0);
+        state.ball.pos = ball;
+        for (id, offset) in [(0, Vec2::new(-30.0, 0.0)), (1, Vec2::new(0.0, -30.0)), (2, Vec2::new(0.0, 30.0))] {
+            state.players[id].pos = ball + offset;
+        }
+        let start = state.players.clone();
+        let mut actions = PlayerArray::<PlayerAction>::default();
+        for id in [0, 1, 2, 3] {
+            actions[id].dir = Vec2::new(0.0, 1.0);
+        }
+        let mut events = Vec::new();
+        eval_tick(&mut state, &conf, actions, &mut match_rng(1), &mut events);
+
+        assert!(events.contains(&RefereeEvent::Hoarding { team: Team::A, players: 3 }));
+        let moved = |id: usize| state.players[id].pos.dist(&start[id].pos);
+        assert!((moved(3) - conf.player.speed).abs() < 1e-4);
+        for id in [0, 1, 2] {
+            assert!((moved(id) - conf.player.speed * conf.hoard.debuf).abs() < 1e-4, "player {id} moved {}", moved(id));
+        }
+    }
+
+    #[test]
+    fn keeper_is_pulled_back_into_its_box() {
+        let mut conf = GameConfig::default();
+        conf.player.goalkeepers = true;
+        let mut state = kicked_off(&conf);
+        assert!(state.players[0].role == PlayerRole::Keeper);
+        state.players[0].pos = conf.field.center() - Vec2::new(50.0, 0.0);
+        let mut actions = PlayerArray::<PlayerAction>::default();
+        actions[0].dir = Vec2::new(1.0, 0.0);
+        tick(&mut state, &conf, actions);
+        assert!(conf.in_own_penalty_box(Team::A, &state.players[0].pos));
+    }
+
+    #[test]
+    fn pass_limit_forfeits_unless_the_team_advances() {
+        use BallPossessionState::*;
+        let conf = GameConfig { ball: BallConfig { pass_limit: 2, ..Default::default() }, ..Default::default() };
+        let passing = |passer| Passing { team: Team::A, passer, launch_tick: 0, shot: false };
+        // passes between players 0 and 1, with the ball at x
+        let pass = |state: &mut GameState, passer: u32, x: f32, events: &mut Vec<RefereeEvent>| {
+            state.ball.pos = Vec2::new(x, conf.field.center().y);
+            state.ball.vel = Vec2::new(0.0, 1.0);
+            state.ball_possession = passing(passer);
+            let prev = Possessed { owner: passer, team: Team::A, capture_ticks: 0, touches: 0 };
+            handle_pass_limit(state, &conf, &prev, &mut match_rng(1), events);
+        };
+
+        let mut state = kicked_off(&conf);
+        let mut events = Vec::new();
+        for passer in [0, 1, 0] {
+            pass(&mut state, passer, 300.0, &mut events);
+        }
+        assert!(events.contains(&RefereeEvent::PassLimit { team: Team::A }));
+        assert_eq!(owner_team(&state), Some(Team::B));
+
+        let mut state = kicked_off(&conf);
+        let mut events = Vec::new();
+        for (passer, x) in [(0, 300.0), (1, 300.0), (0, 800.0), (1, 300.0), (0, 300.0)] {
+            pass(&mut state, passer, x, &mut events);
+        }
+        assert!(events.is_empty());
+        assert_eq!(state.pass_streak.count, 2);
+    }
+
+    #[test]
+    fn running_flat_out_wears_a_player_down() {
+        let mut conf = GameConfig::default();
+        conf.stamina.max = 20.0;
+        let mut state = kicked_off(&conf);
+        let mut actions = PlayerArray::<PlayerAction>::default();
+        actions[1].dir = Vec2::new(1.0, 0.0);
+        let mut step = || {
+            let before = state.players[1].pos;
+            tick(&mut state, &conf, actions.clone());
+            state.players[1].pos.dist(&before)
+        };
+        let fresh = step();
+        let tired = (0..30).map(|_| step()).last().unwrap();
+        assert!((fresh - conf.player.speed).abs() < 1e-4);
+        assert!((tired - conf.player.speed * conf.stamina.exhausted_speed).abs() < 1e-4, "moved {tired}");
+
+
+        // a reset restores it
+        let mut b = [Vec2::ZERO; NUM_PLAYERS as usize];
+        b.iter_mut().for_each(|pos| mirror_pos(pos, &conf));
+        eval_reset(&mut state, &conf, &TeamPair::new([Vec2::ZERO; NUM_PLAYERS as usize], b));
+        assert!(state.players.iter().all(|p| p.stamina == conf.stamina.max));
+    }
+
+    #[test]
+    fn sprint_charge_runs_out_and_recharges() {
+        let mut conf = GameConfig::default();
+        conf.player.sprint_ticks = 2;
+        let mut state = kicked_off(&conf);
+        // player 1 runs right, returning how far it got
+        let run = |state: &mut GameState, sprint: bool| {
+            let mut actions = PlayerArray::<PlayerAction>::default();
+            actions[1] = PlayerAction { dir: Vec2::new(1.0, 0.0), sprint, ..Default::default() };
+            let before = state.players[1].pos;
+            tick(state, &conf, actions);
+            state.players[1].pos.dist(&before)
+        };
+        let (speed, sprint) = (conf.player.speed, conf.player.speed * conf.player.sprint_factor);
+        let moved: Vec<_> = (0..3).map(|_| run(&mut state, true)).collect();
+        assert!(moved.iter().zip([sprint, sprint, speed]).all(|(moved, expected)| (moved - expected).abs() < 1e-4), "{moved:?}");
+        // the failed sprint recharged like any other tick
+        assert_eq!(state.players[1].sprint_charge, conf.player.sprint_recharge);
+
+        // ticks without a sprint refill the charge until a full tick of sprinting is banked
+        while state.players[1].sprint_charge < 1.0 {
+            assert!((run(&mut state, false) - speed).abs() < 1e-4);
+        }
+        assert!((run(&mut state, true) - sprint).abs() < 1e-4);
+    }
+
+    #[test]
+    fn reversing_takes_several_ticks() {
+        let mut conf = GameConfig::default();
+        conf.player.max_accel = conf.player.speed * 0.5;
+        let mut state = kicked_off(&conf);
+        let run = |state: &mut GameState, x: f32| {
+            let mut actions = PlayerArray::<PlayerAction>::default();
+            actions[1].dir = Vec2::new(x, 0.0);
+            tick(state, &conf, actions);
+            state.players[1].vel
+        };
+        // accelerating from a standstill is limited too
+        assert!((run(&mut state, 1.0).x - conf.player.max_accel).abs() < 1e-4);
+        assert!((run(&mut state, 1.0).x - conf.player.speed).abs() < 1e-4);
+
+        let turning: Vec<_> = (0..4).map(|_| run(&mut state, -1.0).x).collect();
+        let expected = [0.5, 0.0, -0.5, -1.0].map(|frac| frac * conf.player.speed);
+        assert!(turning.iter().zip(expected).all(|(vel, expected)| (vel - expected).abs() < 1e-4), "{turning:?}");
+    }
+
+    #[test]
+    fn advantage_is_waived_by_a_shot_and_enforced_otherwise() {
+        let conf = GameConfig { advantage_ticks: 3, ..Default::default() };
+        // team a fouled with the ball, so b is owed it
+        let fouled = || {
+            let mut state = kicked_off(&conf);
+            give_ball(&mut state, 1);
+            state.pending_foul = StateOption::Some(PendingFoul { team: Team::B, ticks_left: conf.advantage_ticks });
+            state
+        };
+
+        let mut state = fouled();
+        let mut events = Vec::new();
+        handle_advantage(&mut state, &conf, &mut match_rng(1), &mut events);
+        state.ball_possession = BallPossessionState::Passing { team: Team::B, passer: NUM_PLAYERS, launch_tick: state.tick, shot: true };
+        handle_advantage(&mut state, &conf, &mut match_rng(1), &mut events);
+        assert_eq!(events, [RefereeEvent::AdvantageWaived { team: Team::B }]);
+        assert!(state.pending_foul == StateOption::None);
+
+        let mut state = fouled();
+        let mut events = Vec::new();
+        for _ in 0..conf.advantage_ticks {
+            assert_eq!(owner_team(&state), Some(Team::A));
+            handle_advantage(&mut state, &conf, &mut match_rng(1), &mut events);
+        }
+        assert_eq!(events, [RefereeEvent::AdvantageLost { team: Team::B }]);
+        assert_eq!(owner_team(&state), Some(Team::B));
+    }
+
+    #[test]
+    fn touch_limit_forces_the_ball_on() {
+        let conf = GameConfig { ball: BallConfig { max_touches: 5, ..Default::default() }, ..Default::default() };
+        let mut state = kicked_off(&conf);
+        state.kickoff_pending = false;
+        give_ball(&mut state, 1);
+        let mut events = Vec::new();
+        for _ in 0..conf.ball.max_touches {
+            handle_touch_limit(&mut state, &conf, &mut match_rng(1), &mut events);
+        }
+        assert_eq!(state.ball_owner(), Some(1));
+        handle_touch_limit(&mut state, &conf, &mut match_rng(1), &mut events);
+        assert_eq!(events, [RefereeEvent::TouchLimit { player: 1 }]);
+        assert_eq!(owner_team(&state), Some(Team::B));
+
+        // passing before the limit hands the receiver a fresh count
+        let mut state = kicked_off(&conf);
+        state.kickoff_pending = false;
+        give_ball(&mut state, 1);
+        let mut events = Vec::new();
+        let mut pass = PlayerArray::<PlayerAction>::default();
+        pass[1].pass = StateOption::Some((state.players[2].pos - state.players[1].pos).normalize_or_zero());
+        for _ in 0..conf.ball.max_touches - 1 {
+            eval_tick(&mut state, &conf, Default::default(), &mut match_rng(1), &mut events);
+        }
+        eval_tick(&mut state, &conf, pass, &mut match_rng(1), &mut events);
+        while state.ball_owner() != Some(2) {
+            assert!(state.tick < 50, "the pass never arrived");
+            eval_tick(&mut state, &conf, Default::default(), &mut match_rng(1), &mut events);
+        }
+        assert!(!events.iter().any(|event| matches!(event, RefereeEvent::TouchLimit { .. })));
+    }
+
+    fn tackle_conf() -> GameConfig {
+        let mut conf = GameConfig::default();
+        conf.player.tackle_range = 20.0;
+        conf
+    }
+
+    // team B's first player carries the ball, team A's player 1 stands a lunge behind it
+    fn tackle_setup(conf: &GameConfig) -> GameState {
+        let mut state = kicked_off(conf);
+        let carrier = NUM_PLAYERS as usize;
+        give_ball(&mut state, carrier as u32);
+        state.players[1].pos = state.players[carrier].pos - Vec2::new(conf.player.tackle_range, 0.0);
+        state
+    }
+
+    fn tackle(dir: Vec2) -> PlayerArray<PlayerAction> {
+        let mut actions = PlayerArray::<PlayerAction>::default();
+        actions[1].tackle = StateOption::Some(dir);
+        actions
+    }
+
+    #[test]
+    fn tackle_takes_the_ball() {
+        let conf = tackle_conf();
+        let mut state = tackle_setup(&conf);
+        let mut events = Vec::new();
+        eval_tick(&mut state, &conf, tackle(Vec2::new(1.0, 0.0)), &mut match_rng(1), &mut events);
+        assert_eq!(state.ball_owner(), Some(1));
+        assert!(events.contains(&RefereeEvent::Tackle { tackler: 1, carrier: NUM_PLAYERS }));
+    }
+
+    #[test]
+    fn missed_tackle_keeps_the_carrier() {
+        let conf = tackle_conf();
+        let mut state = tackle_setup(&conf);
+        tick(&mut state, &conf, tackle(Vec2::new(-1.0, 0.0)));
+        assert_eq!(state.ball_owner(), Some(NUM_PLAYERS));
+        assert_eq!(state.players[1].tackle_cooldown, conf.player.tackle_cooldown);
+    }
+
+    #[test]
+    fn tackle_on_cooldown_is_ignored() {
+        let conf = tackle_conf();
+        let mut state = tackle_setup(&conf);
+        state.players[1].tackle_cooldown = 10;
+        let before = state.players[1].pos;
+        tick(&mut state, &conf, tackle(Vec2::new(1.0, 0.0)));
+        assert_eq!(state.ball_owner(), Some(NUM_PLAYERS));
+        assert_eq!(state.players[1].pos, before);
+
+        // a reset clears it with the other per-player timers
+        let mut b = [Vec2::ZERO; NUM_PLAYERS as usize];
+        b.iter_mut().for_each(|pos| mirror_pos(pos, &conf));
+        eval_reset(&mut state, &conf, &TeamPair::new([Vec2::ZERO; NUM_PLAYERS as usize], b));
+        assert!(state.players.iter().all(|p| p.tackle_cooldown == 0));
+    }
+
+    #[test]
+    fn legal_actions_track_the_tackle_cooldown() {
+        let conf = tackle_conf();
+        let mut state = tackle_setup(&conf);
+        state.players[1].tackle_cooldown = 10;
+        assert!(!state.legal_actions(1, &conf).can_tackle);
+
+        // the cooldown ticks down before tackles are checked, so its last tick is already free
+        state.players[1].tackle_cooldown = 1;
+        assert!(state.legal_actions(1, &conf).can_tackle);
+        tick(&mut state, &conf, tackle(Vec2::new(1.0, 0.0)));
+        assert_eq!(state.ball_owner(), Some(1));
+    }
 }
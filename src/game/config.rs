@@ -1,21 +1,56 @@
+use anyhow::{ bail, Context, Result };
 use serde::{ Serialize, Deserialize };
+use std::path::Path;
 use super::util::*;
+use super::state::{ PlayerRole, ResetCause, Team };
 
 pub const EPSILON: f32 = 0.001;
-pub const COLLISION_MAX_ITERATIONS: u32 = 100;
+/// collision iterations resolved at full strength before collision_damping kicks in
+pub const COLLISION_DAMPING_START: u32 = 3;
+/// players per team. it sizes the shared memory arrays so it can't vary per config, and the
+/// possession logic relies on every team having at least one player. no config can shrink a
+/// team, so validate has no roster to check
 pub const NUM_PLAYERS: u32 = 4;
+const _: () = assert!(NUM_PLAYERS > 0, "every team needs at least one player");
 
 #[derive(Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
 #[repr(C)]
 pub struct BallConfig {
     pub friction: f32,
+    /// friction multipliers for the left, middle and right thirds of the field
+    pub zone_friction: [f32; 3],
     pub radius: f32,
     pub capture_ticks: u32,
+    /// opponents that must be in pickup range at once to accumulate capture ticks
+    pub capturers_required: u32,
     pub stagnation_radius: f32,
-    pub stagnation_ticks: u32
+    pub stagnation_ticks: u32,
+    /// reset the stagnation counter on possession changes and passes, not just movement
+    pub stagnation_reset_on_touch: bool,
+    /// back and forth passes between two teammates before the field resets (0 to disable)
+    pub pass_loop_limit: u32,
+    /// consecutive passes a team can make without shooting or reaching the attacking third
+    /// before it forfeits the ball (0 to disable)
+    pub pass_limit: u32,
+    /// ticks a player can hold the ball before they must pass or shoot, or lose it to the other
+    /// team (0 for unlimited)
+    pub max_touches: u32,
+    /// ticks after a pass is launched during which opponents can't intercept it
+    pub pass_protection_ticks: u32,
+    /// wall bounce restitution, ordered top, bottom, left, right. a bounce also loses the
+    /// friction at the ball squared unless decouple_restitution is set
+    pub restitution: [f32; 4],
+    /// bounce by restitution alone, so a frictionless (friction = 1.0) ball can still lose
+    /// energy on bounces and bounciness doesn't change with friction
+    pub decouple_restitution: bool,
+    /// kicks launch the ball at their speed divided by mass, and each tick of friction is
+    /// applied mass times over, so a heavier ball is slower and stops sooner
+    pub mass: f32,
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
 #[repr(C)]
 pub struct PlayerConfig {
     pub radius: f32, 
@@ -23,28 +58,133 @@ pub struct PlayerConfig {
     pub speed: f32,
     pub pass_speed: f32,
     pub pass_error: f32,
+    /// ball speed of a full strength shot
+    pub shot_speed: f32,
+    /// like pass_error, for shots
+    pub shot_error: f32,
+    /// scales opponents' pickup radius when intercepting a shot
+    pub shot_catch_factor: f32,
+    /// chance per tick that an opponent picks off a pass at pass_speed running straight through
+    /// them. it falls off as the ball passes further from their center and moves faster (0 for
+    /// the closest opponent to always intercept when in reach)
+    pub pass_intercept_base: f32,
+    /// passes with a smaller magnitude (0..1) are ignored and the carrier keeps the ball
+    pub min_pass: f32,
     pub possession_slowdown: f32,
+    /// most a player's velocity can change per tick, in field units, so turning around takes a
+    /// few ticks (0 for instant changes of direction)
+    pub max_accel: f32,
+    /// bias collision corrections away from walls to converge faster in corners
+    pub collision_wall_bias: bool,
+    /// fraction (0..1) of each overlap correction dropped after the first few collision
+    /// iterations, so pileups settle instead of shoving players back and forth (0 to disable)
+    pub collision_damping: f32,
+    /// most collision iterations per tick, at least 1. players still overlapping after this many
+    /// are left as they are and a collisions_unresolved event is raised
+    pub collision_max_iterations: u32,
+    /// ticks a player who lost the ball to a steal or interception can't touch it
+    pub recapture_cooldown: u32,
+    /// closing speed at which an opponent running into the carrier knocks the ball free (0 to disable)
+    pub knockout_speed: f32,
+    /// a defender moving at most this fraction of full speed counts as marking
+    pub marking_speed: f32,
+    /// ticks a defender has to keep marking before the pickup bonus kicks in
+    pub marking_ticks: u32,
+    /// extra pickup radius for a marking defender (0 to disable)
+    pub marking_pickup_bonus: f32,
+    /// the first player of each team is a keeper, confined to its own penalty box
+    pub goalkeepers: bool,
+    pub keeper_pickup_radius: f32,
+    /// how far a full strength tackle lunges (0 to disable tackling)
+    pub tackle_range: f32,
+    /// ticks after a tackle before the player can tackle again
+    pub tackle_cooldown: u32,
+    /// speed multiplier while sprinting. the carrier's extra speed is scaled by possession_slowdown
+    pub sprint_factor: f32,
+    /// ticks of sprinting a full charge lasts (0 to disable sprinting)
+    pub sprint_ticks: u32,
+    /// sprint charge regained on every tick the player isn't sprinting
+    pub sprint_recharge: f32,
 }
 
+impl PlayerConfig {
+    pub fn role_of(&self, id: u32) -> PlayerRole {
+        if self.goalkeepers && id % NUM_PLAYERS == 0 {
+            PlayerRole::Keeper
+        } else {
+            PlayerRole::Field
+        }
+    }
+
+    pub fn pickup_radius_of(&self, role: PlayerRole) -> f32 {
+        match role {
+            PlayerRole::Field => self.pickup_radius,
+            PlayerRole::Keeper => self.keeper_pickup_radius,
+        }
+    }
+}
+
+/// field coordinates have their origin at the top left corner, x increasing toward team b's goal
+/// and y increasing downward. every position in the state, the config and the ipc messages uses
+/// this convention (team b's view is mirrored in x only), so geometry should go through the
+/// helpers below rather than assuming it
 #[derive(Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
 #[repr(C)]
 pub struct FieldConfig {
     pub width: u32,
     pub height: u32,
 }
 
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[repr(u8)]
+pub enum TimeoutPolicy {
+    /// a bot that times out stops all its players for the tick
+    Stop,
+    /// a bot that times out repeats its previous tick's actions
+    Coast,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[repr(u8)]
+pub enum PenaltyBoxRule {
+    /// nobody can enter either penalty box
+    Hard,
+    /// defenders can enter their own box, but staying longer than box_dwell_ticks turns the ball
+    /// over as soon as their team has it
+    Dwell,
+}
+
 #[derive(Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
 #[repr(C)]
 pub struct GoalConfig {
+    /// height of each goal mouth until the endgame, when the goals widen to the full field
     pub normal_height: u32,
+    /// depth of each goal. the ball scores once its edge is this close to the end of the field
     pub thickness: u32,
+    /// the ends of each goal mouth are round posts of radius thickness that the ball bounces off
+    pub solid_posts: bool,
+    /// depth of the zone in front of each goal mouth where a loose ball goes straight to the
+    /// defending keeper, or the closest defender without keepers (0 to disable)
+    pub goal_mouth_depth: u32,
+    /// how far each penalty box reaches into the field from its goal line
     pub penalty_box_width: u32,
+    /// extent of each penalty box along the goal line, centered on the goal
     pub penalty_box_height: u32,
+    /// radius of the rounded corners on each penalty box's field side
     pub penalty_box_radius: u32,
+    /// passes launched at least this far from the goal score long_range_points
+    pub long_range_dist: u32,
+    pub long_range_points: u32,
+    pub penalty_box_rule: PenaltyBoxRule,
+    /// ticks a defender can stay in its own box under PenaltyBoxRule::Dwell
+    pub box_dwell_ticks: u32,
 }
 
 
 impl GoalConfig {
+    /// height of the goal mouths at tick
     pub fn current_height(&self, conf: &GameConfig, tick: u32) -> u32 {
         if tick <= conf.max_ticks {
             self.normal_height
@@ -55,30 +195,337 @@ impl GoalConfig {
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+#[repr(C)]
+pub struct StaminaConfig {
+    /// stamina a player starts each point with (0 to disable)
+    pub max: f32,
+    /// stamina spent per tick at full speed, scaled by how hard the player moves
+    pub drain: f32,
+    /// stamina recovered per tick while resting
+    pub regen: f32,
+    /// movement magnitude (0..1) at or below which a player counts as resting
+    pub rest_speed: f32,
+    /// speed multiplier for a fully exhausted player, rising linearly to 1 at max stamina
+    pub exhausted_speed: f32,
+}
+
+impl StaminaConfig {
+    pub fn speed_factor(&self, stamina: f32) -> f32 {
+        if self.max <= 0.0 {
+            return 1.0;
+        }
+        let t = (stamina / self.max).clamp(0.0, 1.0);
+        self.exhausted_speed + (1.0 - self.exhausted_speed) * t
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
 #[repr(C)]
 pub struct HoardConfig {
+    /// most teammates allowed near the ball before they're slowed (0 to disable)
     pub size: u32,
     pub radius: f32,
+    /// speed multiplier for the players crowding the ball
     pub debuf: f32,
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
 #[repr(C)]
 pub struct GameConfig {
     pub max_ticks: u32,
     pub endgame_ticks: u32,
     pub spawn_ball_dist: f32,
+    /// how far past the halfway line a formation may place a player, still outside
+    /// spawn_ball_dist of the ball
+    pub spawn_halfway_tolerance: f32,
+    /// the first possessor after a reset must pass before the ball is live. shooting or moving
+    /// with the ball turns it over
+    pub kickoff_pass: bool,
+    /// only reset the ball after a goal, leaving players in place
+    pub ball_only_reset_on_goal: bool,
+    /// only reset the ball after stagnation, a pass loop or an offside call, leaving players in place
+    pub ball_only_reset_on_stall: bool,
+    /// a pass received by a teammate who was beyond the last defender when it was played is
+    /// offside, and the field is reset with the ball on the defending team's side
+    pub offside: bool,
+    /// ticks a turnover for a box dwell or pass limit violation waits while the team it favors
+    /// plays on. it's waived if they shoot in that time (0 to turn over at once)
+    pub advantage_ticks: u32,
+    /// send each bot its opponent's remaining compute ticks along with the state
+    pub expose_opponent_compute: bool,
+    pub timeout_policy: TimeoutPolicy,
+    pub hoard: HoardConfig,
+    pub stamina: StaminaConfig,
     pub ball: BallConfig,
     pub player: PlayerConfig,
     pub field: FieldConfig,
     pub goal: GoalConfig,
 }
 
+impl Default for GameConfig {
+    fn default() -> Self {
+        GameConfig {
+            max_ticks: 7200,
+            endgame_ticks: 1000,
+            spawn_ball_dist: 200.0,
+            spawn_halfway_tolerance: 0.0,
+            kickoff_pass: false,
+            ball_only_reset_on_goal: false,
+            ball_only_reset_on_stall: false,
+            offside: false,
+            advantage_ticks: 0,
+            expose_opponent_compute: false,
+            timeout_policy: TimeoutPolicy::Stop,
+            hoard: HoardConfig::default(),
+            stamina: StaminaConfig::default(),
+            ball: BallConfig::default(),
+            player: PlayerConfig::default(),
+            field: FieldConfig::default(),
+            goal: GoalConfig::default(),
+        }
+    }
+}
+
+impl Default for HoardConfig {
+    fn default() -> Self {
+        HoardConfig {
+            size: 0,
+            radius: 60.0,
+            debuf: 0.5,
+        }
+    }
+}
+
+impl Default for StaminaConfig {
+    fn default() -> Self {
+        StaminaConfig {
+            max: 0.0,
+            drain: 1.0,
+            regen: 2.0,
+            rest_speed: 0.1,
+            exhausted_speed: 0.5,
+        }
+    }
+}
+
+impl Default for BallConfig {
+    fn default() -> Self {
+        BallConfig {
+            friction: 0.99,
+            zone_friction: [1.0; 3],
+            radius: 5.0,
+            capture_ticks: 50,
+            capturers_required: 1,
+            stagnation_radius: 30.0,
+            stagnation_ticks: 150,
+            stagnation_reset_on_touch: false,
+            pass_loop_limit: 0,
+            pass_limit: 0,
+            max_touches: 0,
+            pass_protection_ticks: 0,
+            restitution: [1.0; 4],
+            decouple_restitution: false,
+            mass: 1.0,
+        }
+    }
+}
+
+impl Default for PlayerConfig {
+    fn default() -> Self {
+        PlayerConfig {
+            radius: 10.0,
+            pickup_radius: 25.0,
+            speed: 4.0,
+            pass_speed: 12.0,
+            pass_error: 10.0,
+            shot_speed: 18.0,
+            shot_error: 15.0,
+            shot_catch_factor: 0.5,
+            pass_intercept_base: 0.0,
+            min_pass: 0.0,
+            possession_slowdown: 0.75,
+            max_accel: 0.0,
+            collision_wall_bias: false,
+            collision_damping: 0.0,
+            collision_max_iterations: 100,
+            recapture_cooldown: 0,
+            knockout_speed: 0.0,
+            marking_speed: 0.1,
+            marking_ticks: 30,
+            marking_pickup_bonus: 0.0,
+            goalkeepers: false,
+            keeper_pickup_radius: 40.0,
+            tackle_range: 0.0,
+            tackle_cooldown: 60,
+            sprint_factor: 1.5,
+            sprint_ticks: 0,
+            sprint_recharge: 0.25,
+        }
+    }
+}
+
+impl Default for FieldConfig {
+    fn default() -> Self {
+        FieldConfig {
+            width: 1000,
+            height: 600,
+        }
+    }
+}
+
+impl Default for GoalConfig {
+    fn default() -> Self {
+        GoalConfig {
+            normal_height: 150,
+            thickness: 5,
+            solid_posts: false,
+            goal_mouth_depth: 0,
+            penalty_box_width: 91,
+            penalty_box_height: 250,
+            penalty_box_radius: 24,
+            long_range_dist: 400,
+            long_range_points: 1,
+            penalty_box_rule: PenaltyBoxRule::Hard,
+            box_dwell_ticks: 300,
+        }
+    }
+}
+
+impl GameConfig {
+    /// reads a json config, rejecting geometry the engine can't play on. fields missing from the
+    /// file keep their built-in defaults, so configs written before a field existed still load
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read config {}", path.display()))?;
+        let conf: GameConfig = serde_json::from_str(&text)
+            .with_context(|| format!("failed to parse config {}", path.display()))?;
+        conf.validate()
+            .with_context(|| format!("invalid config {}", path.display()))?;
+        Ok(conf)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.field.width == 0 || self.field.height == 0 {
+            bail!("field must be nonzero, got {}x{}", self.field.width, self.field.height);
+        }
+        if self.goal.normal_height > self.field.height {
+            bail!("goal height {} exceeds field height {}", self.goal.normal_height, self.field.height);
+        }
+        if self.ball.mass.is_nan() || self.ball.mass <= 0.0 {
+            bail!("ball mass must be positive, got {}", self.ball.mass);
+        }
+        if !(0.0..1.0).contains(&self.player.collision_damping) {
+            bail!("collision damping must be in 0..1, got {}", self.player.collision_damping);
+        }
+        if self.player.collision_max_iterations == 0 {
+            bail!("collision_max_iterations must be at least 1");
+        }
+        Ok(())
+    }
+
+    /// every leaf field that differs between the two configs, sorted by path. paths are dotted
+    /// like `ball.friction`, and array elements are indexed like `ball.restitution.2`
+    pub fn diff(&self, other: &GameConfig) -> Vec<FieldDiff> {
+        let mut diffs = vec![];
+        if self != other {
+            // through text so f32s keep their short form instead of widening to f64 digits
+            let value = |conf: &GameConfig| -> serde_json::Value {
+                serde_json::from_str(&serde_json::to_string(conf).unwrap()).unwrap()
+            };
+            let (old, new) = (value(self), value(other));
+            diff_values(String::new(), &old, &new, &mut diffs);
+        }
+        diffs
+    }
+
+    pub fn in_own_penalty_box(&self, team: Team, pos: &Vec2) -> bool {
+        let box_width = self.goal.penalty_box_width as f32;
+        let in_x = match team {
+            Team::A => pos.x < self.field.top_left().x + box_width,
+            Team::B => pos.x > self.field.bottom_right().x - box_width,
+        };
+        in_x && (pos.y - self.field.center().y).abs() < self.goal.penalty_box_height as f32 * 0.5
+    }
+
+    /// whether pos is within goal_mouth_depth of team's goal line, level with the goal mouth
+    pub fn in_goal_mouth(&self, team: Team, pos: &Vec2, tick: u32) -> bool {
+        let depth = self.goal.goal_mouth_depth as f32;
+        let in_x = match team {
+            Team::A => pos.x < self.field.top_left().x + depth,
+            Team::B => pos.x > self.field.bottom_right().x - depth,
+        };
+        in_x && (pos.y - self.field.center().y).abs() < self.goal.current_height(self, tick) as f32 * 0.5
+    }
+
+    pub fn ball_only_reset(&self, cause: ResetCause) -> bool {
+        match cause {
+            ResetCause::Goal => self.ball_only_reset_on_goal,
+            ResetCause::Stagnation | ResetCause::PassLoop | ResetCause::Offside => self.ball_only_reset_on_stall,
+            ResetCause::NewGame | ResetCause::Endgame => false,
+        }
+    }
+}
+
+/// one config field that differs, see `GameConfig::diff`
+#[derive(Serialize, Clone, PartialEq, Debug)]
+pub struct FieldDiff {
+    pub path: String,
+    pub old: serde_json::Value,
+    pub new: serde_json::Value,
+}
+
+impl std::fmt::Display for FieldDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {} -> {}", self.path, self.old, self.new)
+    }
+}
+
+fn diff_values(path: String, old: &serde_json::Value, new: &serde_json::Value, diffs: &mut Vec<FieldDiff>) {
+    use serde_json::Value;
+    let join = |key: &dyn std::fmt::Display| if path.is_empty() { key.to_string() } else { format!("{path}.{key}") };
+    match (old, new) {
+        (Value::Object(a), Value::Object(b)) => {
+            for (key, a) in a {
+                diff_values(join(key), a, &b[key], diffs);
+            }
+        }
+        (Value::Array(a), Value::Array(b)) if a.len() == b.len() => {
+            for (i, (a, b)) in a.iter().zip(b).enumerate() {
+                diff_values(join(&i), a, b, diffs);
+            }
+        }
+        _ if old != new => diffs.push(FieldDiff { path, old: old.clone(), new: new.clone() }),
+        _ => {}
+    }
+}
+
+impl BallConfig {
+    pub fn friction_at(&self, field: &FieldConfig, pos: &Vec2) -> f32 {
+        (self.friction * self.zone_friction[field.third(pos.x)]).powf(self.mass)
+    }
+}
+
 impl FieldConfig {
+    /// index of the third containing x, from left to right
+    pub fn third(&self, x: f32) -> usize {
+        ((x * 3.0 / self.width as f32) as usize).min(2)
+    }
+
     pub fn center(&self) -> Vec2 {
         Vec2::new(self.width as f32 * 0.5, self.height as f32 * 0.5)
     }
 
+    pub fn origin(&self) -> Vec2 {
+        Vec2::ZERO
+    }
+
+    pub fn top_left(&self) -> Vec2 {
+        self.origin()
+    }
+
     pub fn bottom_right(&self) -> Vec2 {
         Vec2::new(self.width as f32, self.height as f32)
     }
@@ -91,3 +538,60 @@ impl FieldConfig {
         Vec2::new(self.width as f32, self.height as f32 * 0.5)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load_str(json: &str) -> Result<GameConfig> {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), json).unwrap();
+        GameConfig::load(file.path())
+    }
+
+    #[test]
+    fn missing_fields_keep_their_defaults() {
+        let conf = load_str(r#"{ "max_ticks": 10, "ball": { "friction": 0.5 } }"#).unwrap();
+        let mut expected = GameConfig { max_ticks: 10, ..Default::default() };
+        expected.ball.friction = 0.5;
+        assert!(conf == expected);
+        assert!(load_str("{}").unwrap() == GameConfig::default());
+    }
+
+    #[test]
+    fn config_round_trips_into_a_game() {
+        let mut conf = GameConfig::default();
+        conf.field.width = 1200;
+        conf.goal.penalty_box_rule = PenaltyBoxRule::Dwell;
+        let loaded = load_str(&serde_json::to_string(&conf).unwrap()).unwrap();
+        assert!(loaded == conf);
+        let state = crate::game::state::GameState::new(&loaded);
+        assert_eq!(state.ball.pos, loaded.field.center());
+    }
+
+    #[test]
+    fn bad_geometry_is_rejected() {
+        assert!(load_str(r#"{ "field": { "width": 0, "height": 600 } }"#).is_err());
+        assert!(load_str(r#"{ "goal": { "normal_height": 700 } }"#).is_err());
+        assert!(load_str(r#"{ "player": { "collision_max_iterations": 0 } }"#).is_err());
+    }
+
+    #[test]
+    fn diff_reports_only_the_changed_field() {
+        let default = GameConfig::default();
+        assert!(default.diff(&default).is_empty());
+
+        let mut changed = default.clone();
+        changed.ball.friction = 0.5;
+        let diffs = changed.diff(&default);
+        assert_eq!(diffs.len(), 1, "{diffs:?}");
+        assert_eq!(diffs[0].path, "ball.friction");
+        assert_eq!(diffs[0].old, serde_json::json!(0.5));
+        assert_eq!(diffs[0].new.to_string(), default.ball.friction.to_string());
+
+        let mut changed = default.clone();
+        changed.ball.zone_friction[2] = 0.25;
+        let paths: Vec<_> = default.diff(&changed).into_iter().map(|d| d.path).collect();
+        assert_eq!(paths, ["ball.zone_friction.2"]);
+    }
+}
@@ -2,3 +2,5 @@ pub mod config;
 pub mod state;
 pub mod action;
 pub mod util;
+pub mod stats;
+pub mod sim;
@@ -1,23 +1,18 @@
 use serde::{ Serialize, Deserialize };
-use super::util::Vec2;
+use super::util::{ Fnv1a, Vec2 };
 use super::config::*;
 use std::ops::{ Index, IndexMut };
 
 type PlayerId = u32;
 
-#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
 #[repr(u8, C)]
 pub enum StateOption<T> {
+    #[default]
     None    = 0,
     Some(T) = 1
 }
 
-impl<T> Default for StateOption<T> {
-    fn default() -> Self {
-        StateOption::None
-    }
-}
-
 impl<T> From<StateOption<T>> for Option<T> {
     fn from(value: StateOption<T>) -> Self {
         match value {
@@ -37,6 +32,14 @@ impl<T> From<Option<T>> for StateOption<T> {
 }
 
 impl<T> StateOption<T> {
+    /// the discriminant byte this variant has in shared memory
+    pub fn tag(&self) -> u8 {
+        match self {
+            StateOption::None => 0,
+            StateOption::Some(_) => 1
+        }
+    }
+
     pub fn option(self) -> Option<T> {
         match self {
             StateOption::None => None,
@@ -45,7 +48,7 @@ impl<T> StateOption<T> {
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
 #[repr(u8)]
 pub enum Team{
     A,
@@ -68,7 +71,7 @@ impl Mirror for Team {
 }
 
 
-#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default, Debug)]
 #[repr(C)]
 pub struct TeamPair<T> {
     pub a: T,
@@ -165,6 +168,9 @@ impl<T> IndexMut<Team> for PlayerArray<T> {
     }
 }
 
+/// flips a value into the other team's view: positions reflect across the halfway line,
+/// directions turn around and team a/b data swaps. mirroring twice restores the value, exactly
+/// for everything but positions, see `mirror_pos`
 pub trait Mirror {
     fn mirror(&mut self, conf: &GameConfig);
 }
@@ -182,6 +188,8 @@ impl<T> Mirror for [T; NUM_PLAYERS as usize] where T: Mirror {
     }
 }
 
+/// reflects pos across the halfway line. width - x rounds to the nearest f32, so mirroring twice
+/// can be off by up to half an ulp of the field width (about 3e-5 on a 1000 wide field)
 pub fn mirror_pos(pos: &mut Vec2, conf: &GameConfig) {
     pos.x = conf.field.width as f32 - pos.x;
 }
@@ -201,15 +209,32 @@ impl Mirror for Vec2 {
 }
 
 
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[repr(u8)]
+pub enum PlayerRole {
+    Field,
+    /// confined to its own penalty box, with a larger pickup radius
+    Keeper,
+}
+
 #[derive(Serialize, Deserialize, Clone, PartialEq)]
 #[repr(C)]
 pub struct PlayerState {
     pub id: PlayerId,
     pub pos: Vec2,
     pub dir: Vec2,
+    /// distance moved per tick. equals dir * speed unless PlayerConfig::max_accel limits how fast
+    /// it can follow the requested direction
+    pub vel: Vec2,
     pub speed: f32,
     pub radius: f32,
     pub pickup_radius: f32,
+    pub role: PlayerRole,
+    pub stamina: f32,
+    /// ticks until the player can tackle again
+    pub tackle_cooldown: u32,
+    /// ticks of sprinting the player has banked, see PlayerConfig::sprint_ticks
+    pub sprint_charge: f32,
 }
 
 impl Mirror for PlayerState {
@@ -217,6 +242,7 @@ impl Mirror for PlayerState {
         mirror_player_id(&mut self.id);
         mirror_pos(&mut self.pos, conf);
         self.dir.mirror(conf);
+        self.vel.mirror(conf);
     }
 }
 
@@ -225,15 +251,36 @@ impl Mirror for PlayerState {
 pub struct PlayerAction {
     pub dir: Vec2,
     pub pass: StateOption<Vec2>,
+    /// lunge in this direction to tackle the carrier, the magnitude (0..1) scales the lunge
+    pub tackle: StateOption<Vec2>,
+    /// shoot in this direction, the magnitude (0..1) scales the speed from pass_speed to shot_speed
+    pub shoot: StateOption<Vec2>,
+    /// move at PlayerConfig::sprint_factor times the speed this tick, while sprint charge lasts
+    pub sprint: bool,
 }
 
 impl PlayerAction {
+    /// zeroes non-finite components and shortens every vector to at most unit length, so
+    /// garbage from a bot can't reach the physics
     pub fn sanitize(&mut self) {
-        if !self.dir.x.is_finite() { self.dir.x = 0.0; }
-        if !self.dir.y.is_finite() { self.dir.y = 0.0; }
-        if let StateOption::Some(pass) = &mut self.pass {
-            if !pass.x.is_finite() { pass.x = 0.0; }
-            if !pass.y.is_finite() { pass.y = 0.0; }
+        fn unit(v: &mut Vec2) {
+            if !v.x.is_finite() { v.x = 0.0; }
+            if !v.y.is_finite() { v.y = 0.0; }
+            // scaled down first so huge components can't overflow the norm
+            let scale = v.x.abs().max(v.y.abs());
+            if scale > 1.0 {
+                *v /= scale;
+            }
+            let norm = v.norm();
+            if norm > 1.0 {
+                *v /= norm;
+            }
+        }
+        unit(&mut self.dir);
+        for kick in [&mut self.pass, &mut self.tackle, &mut self.shoot] {
+            if let StateOption::Some(v) = kick {
+                unit(v);
+            }
         }
     }
 }
@@ -244,6 +291,12 @@ impl Mirror for PlayerAction {
         if let StateOption::Some(ref mut pass) = self.pass {
             pass.mirror(conf);
         }
+        if let StateOption::Some(ref mut tackle) = self.tackle {
+            tackle.mirror(conf);
+        }
+        if let StateOption::Some(ref mut shoot) = self.shoot {
+            shoot.mirror(conf);
+        }
     }
 }
 
@@ -251,6 +304,10 @@ impl Mirror for u32 {
     fn mirror(&mut self, _: &GameConfig) { }
 }
 
+impl Mirror for bool {
+    fn mirror(&mut self, _: &GameConfig) { }
+}
+
 pub type TeamAction = [PlayerAction; NUM_PLAYERS as usize];
 pub type PlayerArray<T> = [T; NUM_PLAYERS as usize * 2];
 
@@ -261,8 +318,11 @@ pub enum BallPossessionState {
         owner: PlayerId,
         team: Team,
         capture_ticks: u32,
+        /// ticks the owner has held the ball, see BallConfig::max_touches
+        touches: u32,
     }, 
-    Passing { team: Team },
+    /// the ball is in flight after a pass, or a shot when `shot` is set
+    Passing { team: Team, passer: PlayerId, launch_tick: u32, shot: bool },
     Free
 }
 
@@ -274,7 +334,8 @@ impl Mirror for BallPossessionState {
                 mirror_player_id(owner);
                 team.mirror(conf);
             },
-            Passing { team } => {
+            Passing { team, passer, .. } => {
+                mirror_player_id(passer);
                 team.mirror(conf);
             },
             _ => ()
@@ -282,6 +343,108 @@ impl Mirror for BallPossessionState {
     }
 }
 
+/// why the field is being reset, sent to bots with every reset
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[repr(u8)]
+pub enum ResetCause {
+    /// the opening kickoff of a new match
+    NewGame,
+    Goal,
+    Stagnation,
+    PassLoop,
+    Offside,
+    Endgame,
+}
+
+/// a referee decision, logged as its own json line next to the states so tools don't have to
+/// parse the gamelog comments
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RefereeEvent {
+    /// score is the score after the goal
+    Goal { team: Team, score: TeamPair<u32> },
+    StagnationReset,
+    /// players are back in formation for a kickoff
+    KickoffReset,
+    /// none for a draw
+    GameOver { winner: Option<Team> },
+    Tackle { tackler: u32, carrier: u32 },
+    /// an opponent ran into the carrier fast enough to knock the ball loose
+    Knockout { player: u32, carrier: u32 },
+    GoalMouthClaim { player: u32 },
+    /// a pass shorter than min_pass was refused, the carrier keeps the ball
+    SoftPass { player: u32 },
+    /// team's kickoff taker shot or moved with the ball and lost it
+    KickoffViolation { team: Team },
+    Offside { player: u32 },
+    PassLoopReset,
+    /// team passed more than pass_limit times without advancing
+    PassLimit { team: Team },
+    /// player held the ball for more than max_touches ticks
+    TouchLimit { player: u32 },
+    /// player stayed in its own box for more than box_dwell_ticks
+    BoxDwell { player: u32 },
+    /// a turnover to team waits while team plays on
+    AdvantagePlayed { team: Team },
+    /// team shot in time and the pending turnover is dropped
+    AdvantageWaived { team: Team },
+    /// team didn't shoot in time and gets the ball
+    AdvantageLost { team: Team },
+    /// more than hoard.size of team's players crowd the ball and are slowed
+    Hoarding { team: Team, players: u32 },
+    /// the collision solver ran out of iterations with players still overlapping by up to overlap
+    CollisionsUnresolved { iterations: u32, overlap: f32 },
+}
+
+impl RefereeEvent {
+    /// raised again on every tick its condition holds, rather than once when it happens
+    pub fn repeats(&self) -> bool {
+        matches!(
+            self,
+            RefereeEvent::SoftPass { .. } | RefereeEvent::Hoarding { .. } | RefereeEvent::CollisionsUnresolved { .. }
+        )
+    }
+
+    /// whether two events are the same call for rate limiting. a pileup is one call however far
+    /// its players overlap
+    pub fn same_call(&self, other: &RefereeEvent) -> bool {
+        match (self, other) {
+            (RefereeEvent::CollisionsUnresolved { .. }, RefereeEvent::CollisionsUnresolved { .. }) => true,
+            _ => self == other,
+        }
+    }
+}
+
+/// the gamelog comment for the event
+impl std::fmt::Display for RefereeEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use RefereeEvent::*;
+        let bot = |team: &Team| if *team == Team::A { "A" } else { "B" };
+        match self {
+            Goal { team, score } => write!(f, "Bot {} scored! A: {} B: {}", bot(team), score.a, score.b),
+            StagnationReset => write!(f, "Ball stayed stagnant for too long! Resetting field..."),
+            KickoffReset => write!(f, "Kickoff!"),
+            GameOver { winner: Some(team) } => write!(f, "Game over, Bot {} wins!", bot(team)),
+            GameOver { winner: None } => write!(f, "Game over, it's a tie!"),
+            Tackle { tackler, carrier } => write!(f, "Player {} tackled the ball away from player {}!", tackler, carrier),
+            Knockout { player, carrier } => write!(f, "Player {} knocked the ball loose from player {}!", player, carrier),
+            GoalMouthClaim { player } => write!(f, "Player {} claimed the ball in the goal mouth!", player),
+            SoftPass { player } => write!(f, "Player {} passed too softly! Keeping the ball...", player),
+            KickoffViolation { .. } => write!(f, "Kickoff must be a pass! Turning the ball over..."),
+            Offside { player } => write!(f, "Player {} was offside! Resetting with a free ball...", player),
+            PassLoopReset => write!(f, "Ball passed back and forth for too long! Resetting field..."),
+            PassLimit { team } => write!(f, "Bot {} passed too many times without advancing! Turning the ball over...", bot(team)),
+            TouchLimit { player } => write!(f, "Player {} held the ball for too long! Turning the ball over...", player),
+            BoxDwell { player } => write!(f, "Player {} stayed in the box for too long! Turning the ball over...", player),
+            AdvantagePlayed { team } => write!(f, "Playing advantage for Bot {}...", bot(team)),
+            AdvantageWaived { team } => write!(f, "Advantage paid off for Bot {}! Waiving the turnover...", bot(team)),
+            AdvantageLost { team } => write!(f, "No advantage for Bot {}! Turning the ball over...", bot(team)),
+            Hoarding { team, players } => write!(f, "Bot {} is hoarding the ball with {} players! Slowing them down...", bot(team), players),
+            CollisionsUnresolved { iterations, overlap } => write!(f, "Collisions unresolved after {} iterations! Players still overlap by up to {:.3}", iterations, overlap),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
 #[repr(C)]
 pub struct BallStagnationState {
@@ -295,6 +458,55 @@ impl Mirror for BallStagnationState {
     }
 }
 
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[repr(C)]
+pub struct PassLoopState {
+    /// most recent possessor first
+    pub owners: [StateOption<PlayerId>; 2],
+    pub count: u32,
+}
+
+impl Mirror for PassLoopState {
+    fn mirror(&mut self, _: &GameConfig) {
+        for owner in &mut self.owners {
+            if let StateOption::Some(id) = owner {
+                mirror_player_id(id);
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[repr(C)]
+pub struct PassStreakState {
+    /// the team making the passes
+    pub team: StateOption<Team>,
+    pub count: u32,
+}
+
+impl Mirror for PassStreakState {
+    fn mirror(&mut self, conf: &GameConfig) {
+        if let StateOption::Some(team) = &mut self.team {
+            team.mirror(conf);
+        }
+    }
+}
+
+/// a turnover held back while the team it favors plays on, see GameConfig::advantage_ticks
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[repr(C)]
+pub struct PendingFoul {
+    /// the team the ball goes to if the advantage doesn't pay off
+    pub team: Team,
+    pub ticks_left: u32,
+}
+
+impl Mirror for PendingFoul {
+    fn mirror(&mut self, conf: &GameConfig) {
+        self.team.mirror(conf);
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, PartialEq)]
 #[repr(C)]
 pub struct BallState {
@@ -317,8 +529,28 @@ pub struct GameState {
     pub ball: BallState,
     pub ball_possession: BallPossessionState,
     pub ball_stagnation: BallStagnationState,
+    pub pass_loop: PassLoopState,
+    pub pass_streak: PassStreakState,
+    /// where the ball was last passed from, cleared once someone takes possession
+    pub pass_origin: StateOption<Vec2>,
     pub players: PlayerArray<PlayerState>,
-    pub score: TeamPair<u32>
+    /// ticks until each player can touch the ball again after losing it
+    pub recapture_cooldown: PlayerArray<u32>,
+    /// consecutive ticks each player has spent in their own penalty box
+    pub box_dwell: PlayerArray<u32>,
+    /// consecutive ticks each defender has stood (nearly) still
+    pub marking: PlayerArray<u32>,
+    /// teammates caught beyond the last defender when the ball was last passed, cleared once
+    /// someone takes possession
+    pub offside: PlayerArray<bool>,
+    /// the team awarded the ball at the next reset, after an offside call against the other
+    pub free_ball: StateOption<Team>,
+    pub pending_foul: StateOption<PendingFoul>,
+    pub score: TeamPair<u32>,
+    pub kickoff_pending: bool,
+    /// the opponent's remaining compute ticks, only filled in the copy sent to a bot when the
+    /// config exposes it
+    pub opponent_compute: StateOption<u32>,
 }
 
 impl Mirror for GameState {
@@ -326,7 +558,22 @@ impl Mirror for GameState {
         self.ball.mirror(conf);
         self.ball_possession.mirror(conf);
         self.ball_stagnation.mirror(conf);
+        self.pass_loop.mirror(conf);
+        self.pass_streak.mirror(conf);
+        if let StateOption::Some(origin) = &mut self.pass_origin {
+            mirror_pos(origin, conf);
+        }
         self.players.mirror(conf);
+        self.recapture_cooldown.mirror(conf);
+        self.box_dwell.mirror(conf);
+        self.marking.mirror(conf);
+        self.offside.mirror(conf);
+        if let StateOption::Some(team) = &mut self.free_ball {
+            team.mirror(conf);
+        }
+        if let StateOption::Some(foul) = &mut self.pending_foul {
+            foul.mirror(conf);
+        }
         self.score.mirror(conf);
     }
 }
@@ -347,15 +594,34 @@ impl GameState {
                 center,
                 tick: 0
             },
-            players: std::array::from_fn(|i| PlayerState {
-                id: i as u32,
-                pos: center,
-                dir: Vec2::ZERO,
-                speed: conf.player.speed,
-                radius: conf.player.radius,
-                pickup_radius: conf.player.pickup_radius
+            pass_loop: PassLoopState::default(),
+            pass_streak: PassStreakState::default(),
+            pass_origin: StateOption::None,
+            players: std::array::from_fn(|i| {
+                let role = conf.player.role_of(i as PlayerId);
+                PlayerState {
+                    id: i as u32,
+                    pos: center,
+                    dir: Vec2::ZERO,
+                    vel: Vec2::ZERO,
+                    speed: conf.player.speed,
+                    radius: conf.player.radius,
+                    pickup_radius: conf.player.pickup_radius_of(role),
+                    role,
+                    stamina: conf.stamina.max,
+                    tackle_cooldown: 0,
+                    sprint_charge: conf.player.sprint_ticks as f32,
+                }
             }),
-            score: TeamPair { a: 0, b: 0 }
+            recapture_cooldown: [0; NUM_PLAYERS as usize * 2],
+            box_dwell: [0; NUM_PLAYERS as usize * 2],
+            marking: [0; NUM_PLAYERS as usize * 2],
+            offside: [false; NUM_PLAYERS as usize * 2],
+            free_ball: StateOption::None,
+            pending_foul: StateOption::None,
+            score: TeamPair { a: 0, b: 0 },
+            kickoff_pending: false,
+            opponent_compute: StateOption::None,
         }
     }
 
@@ -384,14 +650,225 @@ impl GameState {
         }
     }
 
-    pub fn teams<'a>(&'a self) -> TeamPair<&'a [PlayerState]> {
+    pub fn teams(&self) -> TeamPair<&[PlayerState]> {
         let (a, b) = self.players.split_at(NUM_PLAYERS as usize);
         TeamPair { a, b }
     }
     
-    pub fn teams_mut<'a>(&'a mut self) -> TeamPair<&'a mut [PlayerState]> {
+    pub fn teams_mut(&mut self) -> TeamPair<&mut [PlayerState]> {
         let (a, b) = self.players.split_at_mut(NUM_PLAYERS as usize);
         TeamPair { a, b }
     }
+
+    /// a hash of every field, equal for equal states on any platform
+    pub fn state_hash(&self) -> u64 {
+        let mut hash = Fnv1a::default();
+        // floats serialize to their shortest exact form, so the json is as precise as the state
+        hash.feed(serde_json::to_string(self).unwrap().as_bytes());
+        hash.0
+    }
+
+    /// a copy with positions and directions flipped into the frame where team attacks toward +x.
+    /// unlike `mirror`, player ids, team labels and the score are left as they are
+    pub fn to_team_frame(&self, team: Team, conf: &GameConfig) -> GameState {
+        let mut state = self.clone();
+        if team == Team::A {
+            return state;
+        }
+        state.ball.mirror(conf);
+        state.ball_stagnation.mirror(conf);
+        if let StateOption::Some(origin) = &mut state.pass_origin {
+            mirror_pos(origin, conf);
+        }
+        for player in state.players.iter_mut() {
+            mirror_pos(&mut player.pos, conf);
+            player.dir.mirror(conf);
+            player.vel.mirror(conf);
+        }
+        state
+    }
+
+    /// what the rules let a player do this tick. works on the mirrored state a bot receives too.
+    /// panics if id isn't a player
+    pub fn legal_actions(&self, id: PlayerId, conf: &GameConfig) -> ActionConstraints {
+        let player = &self.players[id as usize];
+        let team = self.player_team(id).expect("not a player id");
+        let carrying = self.ball_owner() == Some(id);
+        let keeper = player.role == PlayerRole::Keeper;
+        let dwell = conf.goal.penalty_box_rule == PenaltyBoxRule::Dwell;
+        let box_ticks_left = (dwell && !keeper && conf.in_own_penalty_box(team, &player.pos))
+            .then(|| conf.goal.box_dwell_ticks.saturating_sub(self.box_dwell[id as usize]));
+        // cooldowns tick down at the start of eval_tick, before they're checked
+        ActionConstraints {
+            can_pass: carrying,
+            can_shoot: carrying && !self.kickoff_pending,
+            must_pass: carrying && self.kickoff_pending,
+            can_tackle: conf.player.tackle_range > 0.0 && player.tackle_cooldown <= 1,
+            can_sprint: conf.player.sprint_ticks > 0 && player.sprint_charge >= 1.0,
+            can_pick_up: self.recapture_cooldown[id as usize] <= 1,
+            own_box_open: dwell || keeper,
+            confined_to_box: keeper,
+            box_ticks_left,
+        }
+    }
 }
 
+/// a summary of the rule checks eval_tick applies to one player, see `GameState::legal_actions`
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ActionConstraints {
+    /// the player has the ball
+    pub can_pass: bool,
+    /// the player has the ball and isn't taking a kickoff that must be a pass
+    pub can_shoot: bool,
+    /// the player is taking the kickoff: moving or shooting before it passes turns the ball over
+    pub must_pass: bool,
+    /// tackling is enabled and the player's tackle cooldown has run out
+    pub can_tackle: bool,
+    /// sprinting is enabled and the player has at least a tick of sprint charge
+    pub can_sprint: bool,
+    /// the player isn't in a recapture cooldown after losing the ball
+    pub can_pick_up: bool,
+    /// the player may enter its own penalty box. nobody may enter the opponent's
+    pub own_box_open: bool,
+    /// the player is a keeper and can't leave its own penalty box
+    pub confined_to_box: bool,
+    /// ticks the player can still stay in its own box before the ball is turned over, when the
+    /// dwell rule applies to it
+    pub box_ticks_left: Option<u32>,
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn state_option_converts_and_round_trips() {
+        let pass = Vec2::new(0.5, -1.0);
+        assert_eq!(StateOption::from(Some(pass)), StateOption::Some(pass));
+        assert_eq!(StateOption::<Vec2>::from(None), StateOption::None);
+        assert_eq!(StateOption::Some(pass).option(), Some(pass));
+        assert_eq!(Option::<Vec2>::from(StateOption::<Vec2>::None), None);
+        assert_eq!((StateOption::<Vec2>::None.tag(), StateOption::Some(pass).tag()), (0, 1));
+
+        let action = PlayerAction { dir: Vec2::new(1.0, 0.0), pass: Some(pass).into(), ..Default::default() };
+        let json = serde_json::to_string(&action).unwrap();
+        assert!(serde_json::from_str::<PlayerAction>(&json).unwrap() == action);
+    }
+
+    #[test]
+    fn sanitize_neutralizes_garbage() {
+        let mut action = PlayerAction {
+            dir: Vec2::new(f32::NAN, 0.5),
+            pass: StateOption::Some(Vec2::new(f32::INFINITY, f32::NEG_INFINITY)),
+            shoot: StateOption::Some(Vec2::new(3.0, 4.0)),
+            tackle: StateOption::Some(Vec2::new(f32::MAX, f32::MAX)),
+            ..Default::default()
+        };
+        action.sanitize();
+        assert_eq!(action.dir, Vec2::new(0.0, 0.5));
+        assert_eq!(action.pass, StateOption::Some(Vec2::ZERO));
+        let StateOption::Some(shoot) = action.shoot else { panic!("shot dropped") };
+        assert!((shoot - Vec2::new(0.6, 0.8)).norm() < 1e-6);
+        let StateOption::Some(tackle) = action.tackle else { panic!("tackle dropped") };
+        assert!((tackle.norm() - 1.0).abs() < 1e-6 && tackle.x == tackle.y);
+
+        // valid input is left alone
+        let mut valid = PlayerAction { dir: Vec2::new(0.3, -0.4), ..Default::default() };
+        valid.sanitize();
+        assert_eq!(valid.dir, Vec2::new(0.3, -0.4));
+    }
+
+    // walks two serialized states in step, allowing `tol` on the x of a position and nothing else
+    fn assert_close(got: &serde_json::Value, want: &serde_json::Value, tol: f64, in_pos: bool, path: &str) {
+        use serde_json::Value;
+        match (got, want) {
+            (Value::Object(a), Value::Object(b)) => {
+                assert_eq!(a.len(), b.len(), "{path}");
+                for (key, b) in b {
+                    let a = a.get(key).unwrap_or_else(|| panic!("{path}.{key} missing"));
+                    let pos = in_pos || ["pos", "center", "pass_origin"].contains(&key.as_str());
+                    assert_close(a, b, tol, pos, &format!("{path}.{key}"));
+                }
+            }
+            (Value::Array(a), Value::Array(b)) => {
+                assert_eq!(a.len(), b.len(), "{path}");
+                for (i, (a, b)) in a.iter().zip(b).enumerate() {
+                    assert_close(a, b, tol, in_pos, &format!("{path}[{i}]"));
+                }
+            }
+            (Value::Number(a), Value::Number(b)) if in_pos && path.ends_with(".x") => {
+                let (a, b) = (a.as_f64().unwrap(), b.as_f64().unwrap());
+                assert!((a - b).abs() <= tol, "{path}: {a} vs {b}");
+            }
+            _ => assert_eq!(got, want, "{path}"),
+        }
+    }
+
+    #[test]
+    fn mirroring_twice_is_the_identity() {
+        use crate::game::action::{ match_rng, MatchRng };
+        use rand::Rng;
+        let conf = GameConfig::default();
+        for seed in 0..50 {
+            let mut rng = match_rng(seed);
+            let point = |rng: &mut MatchRng| Vec2::new(rng.random_range(0.0..1000.0), rng.random_range(0.0..600.0));
+            let mut state = GameState::new(&conf);
+            state.ball.pos = point(&mut rng);
+            state.ball.vel = point(&mut rng);
+            state.pass_origin = StateOption::Some(point(&mut rng));
+            state.ball_stagnation.center = point(&mut rng);
+            for player in state.players.iter_mut() {
+                player.pos = point(&mut rng);
+                player.dir = point(&mut rng);
+                player.vel = point(&mut rng);
+            }
+            let owner = rng.random_range(0..NUM_PLAYERS * 2);
+            let team = state.player_team(owner).unwrap();
+            state.ball_possession = BallPossessionState::Possessed { owner, team, capture_ticks: 3, touches: 2 };
+            state.recapture_cooldown[owner as usize] = 5;
+            state.offside[rng.random_range(0..NUM_PLAYERS as usize * 2)] = true;
+            state.free_ball = StateOption::Some(team);
+            state.pending_foul = StateOption::Some(PendingFoul { team: team.other(), ticks_left: 4 });
+            state.score = TeamPair::new(rng.random_range(0..5), rng.random_range(5..10));
+
+            let mut mirrored = state.clone();
+            mirrored.mirror(&conf);
+            assert!(mirrored != state);
+            mirrored.mirror(&conf);
+            // width - (width - x) can round away the low bits of a position in the near half, so
+            // position x's come back within an ulp of the field width and everything else exactly
+            let ulp = conf.field.width as f64 * f32::EPSILON as f64;
+            let (got, want) = (serde_json::to_value(&mirrored).unwrap(), serde_json::to_value(&state).unwrap());
+            assert_close(&got, &want, ulp, false, &format!("seed {seed}"));
+
+            let mut action = PlayerAction { dir: point(&mut rng), pass: StateOption::Some(point(&mut rng)), ..Default::default() };
+            let original = action.clone();
+            action.mirror(&conf);
+            action.mirror(&conf);
+            assert!(action == original);
+        }
+    }
+
+    #[test]
+    fn team_frame_flips_positions_but_keeps_labels() {
+        let conf = GameConfig::default();
+        let mut state = GameState::new(&conf);
+        state.score = TeamPair::new(2, 1);
+        state.ball.pos = Vec2::new(100.0, 50.0);
+        state.ball.vel = Vec2::new(3.0, 1.0);
+        state.players[0].pos = Vec2::new(200.0, 300.0);
+        state.ball_possession = BallPossessionState::Possessed { owner: 0, team: Team::A, capture_ticks: 0, touches: 0 };
+
+        let framed = state.to_team_frame(Team::B, &conf);
+        assert_eq!(framed.ball.pos, Vec2::new(conf.field.width as f32 - 100.0, 50.0));
+        assert_eq!(framed.ball.vel, Vec2::new(-3.0, 1.0));
+        assert_eq!(framed.players[0].pos, Vec2::new(conf.field.width as f32 - 200.0, 300.0));
+        assert_eq!(framed.score, state.score);
+        assert_eq!(framed.ball_owner(), Some(0));
+        assert!(framed.ball_possession == state.ball_possession);
+
+        // team a already attacks toward +x
+        assert_eq!(state.to_team_frame(Team::A, &conf).ball.pos, state.ball.pos);
+    }
+}
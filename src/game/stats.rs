@@ -0,0 +1,406 @@
+use serde::{ Serialize, Deserialize };
+use super::{action::MatchRng, config::*, state::*, util::*};
+use rand::Rng;
+use std::cmp::Ordering;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[repr(u8)]
+pub enum TieBreak {
+    Goals,
+    Possession,
+    Shots,
+    Coin,
+}
+
+/// what settled a match that has a winner
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DecidedBy {
+    Score,
+    /// the other bot exited mid match
+    Forfeit,
+    TieBreak(TieBreak),
+}
+
+impl std::fmt::Display for DecidedBy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Score => write!(f, "score"),
+            Self::Forfeit => write!(f, "forfeit"),
+            Self::TieBreak(tie_break) => write!(f, "{:?} tie break", tie_break),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct MatchStats {
+    pub possession_ticks: TeamPair<u32>,
+    pub shots: TeamPair<u32>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct MatchResult {
+    pub match_id: String,
+    pub seed: u64,
+    pub score: TeamPair<u32>,
+    pub winner: Option<Team>,
+    pub decided_by: Option<DecidedBy>,
+    pub cancelled: bool,
+    /// missing from results recorded before teams had names
+    #[serde(default)]
+    pub teams: TeamPair<TeamInfo>,
+    /// rolling hash over every tick's state, see `GameState::state_hash`. missing from results
+    /// recorded before it was added
+    #[serde(default)]
+    pub checksum: String,
+}
+
+/// the complete record of one match, for archiving alongside or instead of the gamelog
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct MatchSummary {
+    #[serde(flatten)]
+    pub result: MatchResult,
+    /// ticks played, including overtime
+    pub ticks: u32,
+    /// wall-clock seconds from the handshake to the end of the match
+    pub elapsed_secs: f64,
+    pub stats: MatchStats,
+    pub config: GameConfig,
+}
+
+/// how a team is shown in logs and renders
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Default, Debug)]
+pub struct TeamInfo {
+    pub name: String,
+    /// an svg color
+    pub color: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum PossessionEventKind {
+    /// picked up a free ball
+    Pickup,
+    /// took the ball off an opponent carrying it
+    Steal,
+    Pass,
+    /// caught a teammate's pass
+    Reception,
+    /// caught an opponent's pass
+    Interception,
+    /// the ball went free, from a missed pass or a knockout
+    Loose,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct PossessionEvent {
+    pub tick: u32,
+    pub kind: PossessionEventKind,
+    /// the player gaining the ball, or the one losing it for passes and loose balls
+    pub player: u32,
+    pub team: Team,
+}
+
+/// play-by-play of every change in ball control over a match
+#[derive(Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct PossessionLog {
+    pub events: Vec<PossessionEvent>,
+}
+
+impl PossessionLog {
+    /// call once per tick after eval_tick, with the possession state from before the tick. a tick
+    /// that chains several transitions (e.g., a pass caught on launch) logs each step
+    pub fn update(&mut self, prev: &BallPossessionState, state: &GameState) {
+        use PossessionEventKind::*;
+        let cur = &state.ball_possession;
+        let (before, after) = (holder(prev), holder(cur));
+        let (was_passing, is_passing) = (is_passing(prev), is_passing(cur));
+        let mut push = |kind, (player, team)| self.events.push(PossessionEvent { tick: state.tick, kind, player, team });
+
+        if let Some(gainer @ (_, team)) = after.filter(|_| after != before) {
+            let kind = match before {
+                None => Pickup,
+                Some((_, prev_team)) if prev_team != team && was_passing => Interception,
+                Some(_) if was_passing => Reception,
+                Some((_, prev_team)) if prev_team != team => Steal,
+                // a carrier's pass reached a teammate within the tick
+                Some(passer) => {
+                    push(Pass, passer);
+                    Reception
+                }
+            };
+            push(kind, gainer);
+        }
+        if let Some(passer) = after.filter(|_| is_passing && (!was_passing || after != before)) {
+            push(Pass, passer);
+        }
+        if let (Some(loser), None) = (before, after) {
+            push(Loose, loser);
+        }
+    }
+}
+
+fn holder(possession: &BallPossessionState) -> Option<(u32, Team)> {
+    match *possession {
+        BallPossessionState::Possessed { owner, team, .. } => Some((owner, team)),
+        BallPossessionState::Passing { passer, team, .. } => Some((passer, team)),
+        BallPossessionState::Free => None,
+    }
+}
+
+fn is_passing(possession: &BallPossessionState) -> bool {
+    matches!(possession, BallPossessionState::Passing { .. })
+}
+
+impl MatchStats {
+    /// call once per tick after eval_tick, with the possession state from before the tick
+    pub fn update(&mut self, prev: &BallPossessionState, state: &GameState, conf: &GameConfig) {
+        use BallPossessionState::*;
+        match (prev, &state.ball_possession) {
+            (Possessed { .. }, Passing { team, shot, .. }) => {
+                if *shot || is_shot(state, conf, *team) {
+                    self.shots[*team] += 1;
+                }
+            }
+            (_, Possessed { team, .. }) => {
+                self.possession_ticks[*team] += 1;
+            }
+            _ => ()
+        }
+    }
+}
+
+// a pass counts as a shot if its line of travel crosses the opponent's goal mouth
+pub(crate) fn is_shot(state: &GameState, conf: &GameConfig, team: Team) -> bool {
+    let goal = match team {
+        Team::A => conf.field.goal_b(),
+        Team::B => conf.field.goal_a(),
+    };
+    let Vec2 { x: vx, y: vy } = state.ball.vel;
+    if vx == 0.0 {
+        return false;
+    }
+    let t = (goal.x - state.ball.pos.x) / vx;
+    if t <= 0.0 {
+        return false;
+    }
+    let y = state.ball.pos.y + vy * t;
+    let h = conf.goal.current_height(conf, state.tick) as f32;
+    (y - goal.y).abs() <= h * 0.5
+}
+
+impl MatchResult {
+    pub fn cancelled(match_id: &str, seed: u64, score: &TeamPair<u32>) -> Self {
+        MatchResult {
+            match_id: match_id.to_string(),
+            seed,
+            score: *score,
+            winner: None,
+            decided_by: None,
+            cancelled: true,
+            teams: TeamPair::default(),
+            checksum: String::new(),
+        }
+    }
+
+    /// the match ended because the bots in forfeited exited. the other bot wins, with
+    /// default_score (winner:loser) replacing the score when given. ties if both exited
+    pub fn forfeit(
+        match_id: &str,
+        seed: u64,
+        score: &TeamPair<u32>,
+        forfeited: TeamPair<bool>,
+        default_score: Option<TeamPair<u32>>,
+    ) -> Self {
+        let winner = match (forfeited.a, forfeited.b) {
+            (false, true) => Some(Team::A),
+            (true, false) => Some(Team::B),
+            _ => None,
+        };
+        let score = match (winner, default_score) {
+            (Some(Team::A), Some(default)) => default,
+            (Some(Team::B), Some(default)) => TeamPair::new(default.b, default.a),
+            _ => *score,
+        };
+        MatchResult {
+            match_id: match_id.to_string(),
+            seed,
+            score,
+            winner,
+            decided_by: winner.map(|_| DecidedBy::Forfeit),
+            cancelled: false,
+            teams: TeamPair::default(),
+            checksum: String::new(),
+        }
+    }
+
+    /// goals always decide first, the tie breaks are then tried in order
+    pub fn decide(match_id: &str, seed: u64, score: &TeamPair<u32>, stats: &MatchStats, tie_breaks: &[TieBreak], rng: &mut MatchRng) -> Self {
+        let mut result = MatchResult {
+            match_id: match_id.to_string(),
+            seed,
+            score: *score,
+            winner: None,
+            decided_by: None,
+            cancelled: false,
+            teams: TeamPair::default(),
+            checksum: String::new(),
+        };
+        let lead = |a: u32, b: u32| match a.cmp(&b) {
+            Ordering::Greater => Some(Team::A),
+            Ordering::Less => Some(Team::B),
+            Ordering::Equal => None,
+        };
+        if let Some(winner) = lead(score.a, score.b) {
+            result.winner = Some(winner);
+            result.decided_by = Some(DecidedBy::Score);
+            return result;
+        }
+        for tie_break in tie_breaks {
+            let (a, b) = match tie_break {
+                TieBreak::Goals => (score.a, score.b),
+                TieBreak::Possession => (stats.possession_ticks.a, stats.possession_ticks.b),
+                TieBreak::Shots => (stats.shots.a, stats.shots.b),
+                TieBreak::Coin => {
+                    if rng.random_bool(0.5) { (1, 0) } else { (0, 1) }
+                }
+            };
+            if let Some(winner) = lead(a, b) {
+                result.winner = Some(winner);
+                result.decided_by = Some(DecidedBy::TieBreak(*tie_break));
+                break;
+            }
+        }
+        result
+    }
+}
+
+/// results of a series of matches between the same two bots. pairs are keyed by bot (the one
+/// given first and the one given second), not by the side each played in a given match
+#[derive(Serialize, Deserialize, Clone, PartialEq, Default, Debug)]
+pub struct SeriesTally {
+    pub games: u32,
+    pub wins: TeamPair<u32>,
+    pub ties: u32,
+    pub cancelled: u32,
+    pub goals: TeamPair<u32>,
+}
+
+impl SeriesTally {
+    /// swapped is whether the first bot played as team b
+    pub fn add(&mut self, result: &MatchResult, swapped: bool) {
+        let by_bot = |pair: TeamPair<u32>| if swapped { TeamPair::new(pair.b, pair.a) } else { pair };
+        self.games += 1;
+        let goals = by_bot(result.score);
+        self.goals.a += goals.a;
+        self.goals.b += goals.b;
+        match (result.cancelled, result.winner) {
+            (true, _) => self.cancelled += 1,
+            (false, None) => self.ties += 1,
+            (false, Some(winner)) => {
+                let first_won = (winner == Team::A) != swapped;
+                if first_won {
+                    self.wins.a += 1;
+                } else {
+                    self.wins.b += 1;
+                }
+            }
+        }
+    }
+
+    /// the first bot's goals minus the second's
+    pub fn goal_difference(&self) -> i64 {
+        self.goals.a as i64 - self.goals.b as i64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::action::match_rng;
+
+    #[test]
+    fn goal_tie_decided_by_possession() {
+        let stats = MatchStats {
+            possession_ticks: TeamPair::new(300, 500),
+            shots: TeamPair::new(4, 1),
+        };
+        let score = TeamPair::new(2, 2);
+        let result = MatchResult::decide("", 1, &score, &stats, &[TieBreak::Possession], &mut match_rng(1));
+        assert_eq!(result.winner, Some(Team::B));
+        assert_eq!(result.decided_by, Some(DecidedBy::TieBreak(TieBreak::Possession)));
+
+        // without tie breaks the same match is a tie
+        let result = MatchResult::decide("", 1, &score, &stats, &[], &mut match_rng(1));
+        assert_eq!(result.winner, None);
+        assert_eq!(result.decided_by, None);
+    }
+
+    #[test]
+    fn goals_decide_before_tie_breaks() {
+        let stats = MatchStats { possession_ticks: TeamPair::new(300, 500), shots: TeamPair::new(4, 1) };
+        let result = MatchResult::decide("", 1, &TeamPair::new(3, 2), &stats, &[TieBreak::Possession], &mut match_rng(1));
+        assert_eq!(result.winner, Some(Team::A));
+        assert_eq!(result.decided_by, Some(DecidedBy::Score));
+    }
+
+    #[test]
+    fn forfeit_awards_the_other_team() {
+        let score = TeamPair::new(1, 3);
+        let result = MatchResult::forfeit("", 1, &score, TeamPair::new(false, true), None);
+        assert_eq!(result.winner, Some(Team::A));
+        assert_eq!(result.decided_by, Some(DecidedBy::Forfeit));
+        assert!(result.score == score);
+
+        let result = MatchResult::forfeit("", 1, &score, TeamPair::new(false, true), Some(TeamPair::new(3, 0)));
+        assert!(result.score == TeamPair::new(3, 0));
+
+        // both bots gone is a tie
+        let result = MatchResult::forfeit("", 1, &score, TeamPair::new(true, true), None);
+        assert_eq!(result.winner, None);
+        assert_eq!(result.decided_by, None);
+    }
+
+    #[test]
+    fn wide_shot_still_counts() {
+        use BallPossessionState::*;
+        let conf = GameConfig::default();
+        let mut state = GameState::new(&conf);
+        state.ball.vel = Vec2::new(0.0, 10.0);
+        state.ball_possession = Passing { team: Team::A, passer: 1, launch_tick: 0, shot: true };
+        let mut stats = MatchStats::default();
+        stats.update(&Possessed { owner: 1, team: Team::A, capture_ticks: 0, touches: 0 }, &state, &conf);
+        assert_eq!(stats.shots.a, 1);
+    }
+
+    #[test]
+    fn possession_log_follows_the_play() {
+        use BallPossessionState::*;
+        let conf = GameConfig::default();
+        let held = |owner, team| Possessed { owner, team, capture_ticks: 0, touches: 0 };
+        let passed = |passer, team| Passing { team, passer, launch_tick: 0, shot: false };
+        // pickup, pass and reception, a pass intercepted by b, a steal back and a knockout
+        let play = [
+            Free, held(1, Team::A), passed(1, Team::A), held(2, Team::A), passed(2, Team::A),
+            held(5, Team::B), held(1, Team::A), Free,
+        ];
+
+        let mut log = PossessionLog::default();
+        let mut state = GameState::new(&conf);
+        for (tick, possession) in play.iter().enumerate().skip(1) {
+            state.tick = tick as u32;
+            state.ball_possession = possession.clone();
+            log.update(&play[tick - 1], &state);
+        }
+        let events: Vec<_> = log.events.iter().map(|e| (e.tick, e.kind, e.player, e.team)).collect();
+        use PossessionEventKind::*;
+        assert_eq!(events, [
+            (1, Pickup, 1, Team::A),
+            (2, Pass, 1, Team::A),
+            (3, Reception, 2, Team::A),
+            (4, Pass, 2, Team::A),
+            (5, Interception, 5, Team::B),
+            (6, Steal, 1, Team::A),
+            (7, Loose, 1, Team::A),
+        ]);
+    }
+}
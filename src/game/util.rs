@@ -98,6 +98,21 @@ impl Vec2 {
     pub fn dist(&self, other: &Vec2) -> f32 {
         (*other - *self).norm()
     }
+
+    #[inline(always)]
+    pub fn lerp(self, other: Vec2, t: f32) -> Self {
+        self + (other - self) * t
+    }
+
+    /// moves at most max_delta toward target without overshooting
+    pub fn move_toward(self, target: Vec2, max_delta: f32) -> Self {
+        let delta = target - self;
+        let dist = delta.norm();
+        if dist <= max_delta || dist == 0.0 {
+            return target;
+        }
+        self + delta / dist * max_delta
+    }
 }
 
 impl Add<Vec2> for Vec2 {
@@ -193,3 +208,56 @@ impl Default for Vec2 {
         Vec2::ZERO
     }
 }
+
+/// fnv-1a, stable across platforms and compiler versions
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Fnv1a(pub u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Fnv1a(0xcbf29ce484222325)
+    }
+}
+
+impl Fnv1a {
+    pub fn feed(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0 ^= *b as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lerp_endpoints() {
+        let a = Vec2::new(1.0, -2.0);
+        let b = Vec2::new(5.0, 6.0);
+        assert_eq!(a.lerp(b, 0.0), a);
+        assert_eq!(a.lerp(b, 1.0), b);
+        assert_eq!(a.lerp(b, 0.5), Vec2::new(3.0, 2.0));
+    }
+
+    #[test]
+    fn move_toward_steps_by_max_delta() {
+        let moved = Vec2::ZERO.move_toward(Vec2::new(10.0, 0.0), 3.0);
+        assert_eq!(moved, Vec2::new(3.0, 0.0));
+    }
+
+    #[test]
+    fn move_toward_clamps_at_target() {
+        let target = Vec2::new(3.0, 4.0);
+        assert_eq!(Vec2::ZERO.move_toward(target, 5.0), target);
+        assert_eq!(Vec2::ZERO.move_toward(target, 100.0), target);
+    }
+
+    #[test]
+    fn move_toward_zero_distance() {
+        let p = Vec2::new(2.0, 2.0);
+        assert_eq!(p.move_toward(p, 1.0), p);
+        assert_eq!(p.move_toward(p, 0.0), p);
+    }
+}
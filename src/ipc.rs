@@ -1,22 +1,29 @@
 use anyhow::Context;
 use memmap::MmapMut;
 use pastey::paste;
+use serde::{ Serialize, Deserialize };
 use std::{
     fs::OpenOptions,
     hint,
-    mem::offset_of,
+    io,
+    mem::{offset_of, MaybeUninit},
+    net::{IpAddr, SocketAddr},
     ops::Drop,
     path::Path,
     sync::atomic::{AtomicU8, Ordering},
     time::Duration,
 };
 use crate::game::{
-    util::Vec2,
+    util::{ Fnv1a, Vec2 },
     config::{ GameConfig, NUM_PLAYERS },
-    state::{ Team, GameState, PlayerAction, TeamPair },
+    state::{ Team, GameState, PlayerAction, ResetCause, StateOption, TeamPair },
 };
 use thiserror::Error;
-use tokio::time;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    time,
+};
 
 #[repr(u8)]
 pub enum EngineStatus {
@@ -42,7 +49,7 @@ macro_rules! define_protocols {
         pub trait Protocol {
             const ID: ProtocolId;
             type Msg;
-            type Response;
+            type Response: Validate;
             fn msg_into_enum(msg: Self::Msg) -> ProtocolUnion;
             fn response_into_enum(response: Self::Response) -> ProtocolUnion;
             fn enum_into_msg(variant: ProtocolUnion) -> Self::Msg;
@@ -85,8 +92,9 @@ macro_rules! define_protocols {
                     }
                 }
             )*
-            #[derive(Clone)]
+            #[derive(Clone, Serialize, Deserialize)]
             #[repr(u8, C)]
+            #[allow(clippy::large_enum_variant)]
             pub enum ProtocolUnion {
                 $(
                     [<$name Msg>]($msg),
@@ -94,6 +102,46 @@ macro_rules! define_protocols {
                 )*
             }
 
+            impl ProtocolUnion {
+                /// the tag byte this variant has in shared memory
+                pub fn discriminant(&self) -> u8 {
+                    match self {
+                        $(
+                            ProtocolUnion::[<$name Msg>](_) => [<$name Protocol>]::msg_discriminant(),
+                            ProtocolUnion::[<$name Response>](_) => [<$name Protocol>]::response_discriminant(),
+                        )*
+                    }
+                }
+            }
+
+            pub const PROTOCOLS: &[ProtocolInfo] = &[
+                $(
+                    ProtocolInfo {
+                        name: stringify!($name),
+                        id: ProtocolId::$name,
+                        msg: stringify!($msg),
+                        msg_size: size_of::<$msg>(),
+                        response: stringify!($resp),
+                        response_size: size_of::<$resp>(),
+                    },
+                )*
+            ];
+
+            /// offset of the variant payload within ProtocolUnion. the enum is laid out as a u8 tag
+            /// followed by a union of the payloads, so this is the largest payload alignment
+            pub const PROTOCOL_PAYLOAD_OFFSET: usize = {
+                let mut align = 1;
+                $(
+                    if align_of::<$msg>() > align {
+                        align = align_of::<$msg>();
+                    }
+                    if align_of::<$resp>() > align {
+                        align = align_of::<$resp>();
+                    }
+                )*
+                align
+            };
+
             pub struct Strategy {
                 $(
                     pub [<on_ $name:lower>]: Box<dyn Fn(&$msg) -> $resp>,
@@ -114,30 +162,131 @@ macro_rules! define_protocols {
     };
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 #[repr(C)]
 pub struct ResetMsg {
     pub score: TeamPair<u32>,
-    pub config: GameConfig,
+    pub cause: ResetCause,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 #[repr(C)]
 pub struct HandshakeMsg{
     pub team: Team,
+    /// the engine's PROTOCOL_VERSION
+    pub version: u32,
     pub config: GameConfig
 }
 
-type Score = TeamPair<u32>;
-
 pub const HANDSHAKE_BOT: u64 = 0xabe119c019aaffcc;
 
+/// bumped whenever the layout of a message or response changes
+pub const PROTOCOL_VERSION: u32 = 3;
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[repr(C)]
+pub struct HandshakeResponse {
+    /// must be HANDSHAKE_BOT
+    pub magic: u64,
+    /// the protocol version the bot was built against
+    pub version: u32,
+}
+
+impl HandshakeResponse {
+    /// the response a bot built against this crate should give
+    pub const CURRENT: Self = HandshakeResponse { magic: HANDSHAKE_BOT, version: PROTOCOL_VERSION };
+}
+
 define_protocols! {
-    Handshake: (HandshakeMsg, u64),
-    Reset: (Score, [Vec2; NUM_PLAYERS as usize]),
+    Handshake: (HandshakeMsg, HandshakeResponse),
+    Reset: (ResetMsg, [Vec2; NUM_PLAYERS as usize]),
     Tick: (GameState, [PlayerAction; NUM_PLAYERS as usize])
 }
 
+pub struct ProtocolInfo {
+    pub name: &'static str,
+    pub id: ProtocolId,
+    pub msg: &'static str,
+    pub msg_size: usize,
+    pub response: &'static str,
+    pub response_size: usize,
+}
+
+impl ProtocolInfo {
+    pub fn msg_discriminant(&self) -> u8 {
+        self.id as u8 * 2
+    }
+
+    pub fn response_discriminant(&self) -> u8 {
+        self.id as u8 * 2 + 1
+    }
+}
+
+/// names the protocol message a discriminant byte belongs to, e.g. "Tick response"
+pub fn describe_discriminant(discriminant: u8) -> String {
+    match PROTOCOLS.get(discriminant as usize / 2) {
+        Some(p) if discriminant % 2 == 0 => format!("{} msg", p.name),
+        Some(p) => format!("{} response", p.name),
+        None => format!("unknown discriminant 0x{:x}", discriminant),
+    }
+}
+
+/// human readable description of the shared memory wire contract
+pub fn protocol_table() -> String {
+    let mut out = format!(
+        "shm: {} bytes, sync at 0x{:x}, fnv-1a checksum at 0x{:x}, protocol at 0x{:x}\nprotocol union: {} bytes, tag at 0x0, payload at 0x{:x}\n",
+        SHM_SIZE,
+        offset_of!(Shm, sync),
+        offset_of!(Shm, checksum),
+        offset_of!(Shm, protocol),
+        size_of::<ProtocolUnion>(),
+        PROTOCOL_PAYLOAD_OFFSET,
+    );
+    for p in PROTOCOLS {
+        out += &format!(
+            "{} (id {}): msg {} = {} ({} bytes), response {} = {} ({} bytes)\n",
+            p.name,
+            p.id as u8,
+            p.msg_discriminant(),
+            p.msg,
+            p.msg_size,
+            p.response_discriminant(),
+            p.response,
+            p.response_size,
+        );
+    }
+    out
+}
+
+/// checks that bytes written by an untrusted bot form a valid value before they are read as one.
+/// `bytes` is always exactly `size_of::<Self>()` long
+pub trait Validate: Sized {
+    fn validate(_bytes: &[u8]) -> bool {
+        true
+    }
+}
+
+impl Validate for u64 {}
+impl Validate for HandshakeResponse {}
+impl Validate for Vec2 {}
+
+impl Validate for PlayerAction {
+    fn validate(bytes: &[u8]) -> bool {
+        // StateOption is a u8 tag followed by its payload, and a bool must be 0 or 1
+        let max_tag = StateOption::<Vec2>::Some(Vec2::ZERO).tag();
+        [offset_of!(PlayerAction, pass), offset_of!(PlayerAction, tackle), offset_of!(PlayerAction, shoot)]
+            .iter()
+            .all(|offset| bytes[*offset] <= max_tag)
+            && bytes[offset_of!(PlayerAction, sprint)] <= 1
+    }
+}
+
+impl<T: Validate, const N: usize> Validate for [T; N] {
+    fn validate(bytes: &[u8]) -> bool {
+        bytes.chunks_exact(size_of::<T>()).all(T::validate)
+    }
+}
+
 #[derive(Error, Debug)]
 #[repr(C, u8)]
 pub enum ResponseError {
@@ -149,6 +298,10 @@ pub enum ResponseError {
     SizeMismatch { expected: usize, actual: usize } = 2,
     #[error("response timed out")]
     Timeout(#[from] time::error::Elapsed) = 3,
+    #[error("wrong protocol (expected {expected}, got {actual})")]
+    WrongProtocol { expected: String, actual: String } = 4,
+    #[error("connection failed: {0}")]
+    Connection(#[from] io::Error) = 5,
 }
 
 pub type ResponseResult<T> = Result<T, ResponseError>;
@@ -156,9 +309,13 @@ pub type ResponseResult<T> = Result<T, ResponseError>;
 #[repr(C)]
 struct Shm {
     sync: AtomicU8,
+    /// fnv-1a of the protocol bytes, written by the bot along with each response
+    checksum: u64,
     protocol: ProtocolUnion,
 }
 
+pub const SHM_SIZE: usize = size_of::<Shm>();
+
 #[inline(never)]
 async fn poll(au8: &AtomicU8, cmp: u8) {
     for i in 0.. {
@@ -176,10 +333,73 @@ async fn poll(au8: &AtomicU8, cmp: u8) {
 
 // safe because we only grab one byte
 #[inline]
-fn deref_sync<'a>(mmap: &'a [u8]) -> &'a AtomicU8 {
+fn deref_sync(mmap: &[u8]) -> &AtomicU8 {
     unsafe { &*(mmap.as_ptr().add(offset_of!(Shm, sync)) as *const AtomicU8) }
 }
 
+fn protocol_checksum(protocol: &[u8]) -> u64 {
+    let mut hash = Fnv1a::default();
+    hash.feed(protocol);
+    hash.0
+}
+
+/// stores the checksum of the protocol bytes in the header, as a bot does after writing its
+/// response. `shm` must be `SHM_SIZE` bytes long
+pub fn seal_response(shm: &mut [u8]) {
+    let checksum = protocol_checksum(&shm[offset_of!(Shm, protocol)..][..size_of::<ProtocolUnion>()]);
+    shm[offset_of!(Shm, checksum)..][..size_of::<u64>()].copy_from_slice(&checksum.to_ne_bytes());
+}
+
+/// reads a bot's response out of a raw shared memory region. the bot is untrusted, so the bytes
+/// are copied out first and validated before being interpreted
+pub fn read_response<T: Protocol>(shm: &[u8]) -> ResponseResult<T::Response> {
+    let addr = shm.as_ptr() as usize;
+    let len = shm.len();
+    let align = align_of::<Shm>();
+
+    if len != size_of::<Shm>() {
+        return Err(ResponseError::SizeMismatch {
+            expected: size_of::<Shm>(),
+            actual: len,
+        });
+    }
+    if addr % align != 0 {
+        return Err(ResponseError::AlignmentError {
+            address: addr,
+            alignment: align,
+        });
+    }
+
+    let mut union = MaybeUninit::<ProtocolUnion>::uninit();
+    let bytes = unsafe {
+        std::ptr::copy_nonoverlapping(
+            shm.as_ptr().add(offset_of!(Shm, protocol)),
+            union.as_mut_ptr() as *mut u8,
+            size_of::<ProtocolUnion>()
+        );
+        std::slice::from_raw_parts(union.as_ptr() as *const u8, size_of::<ProtocolUnion>())
+    };
+
+    let checksum = &shm[offset_of!(Shm, checksum)..][..size_of::<u64>()];
+    if u64::from_ne_bytes(checksum.try_into().unwrap()) != protocol_checksum(bytes) {
+        return Err(ResponseError::Malformed);
+    }
+
+    if bytes[0] != T::response_discriminant() {
+        return Err(ResponseError::WrongProtocol {
+            expected: describe_discriminant(T::response_discriminant()),
+            actual: describe_discriminant(bytes[0]),
+        });
+    }
+    let payload = &bytes[PROTOCOL_PAYLOAD_OFFSET..][..size_of::<T::Response>()];
+    if !T::Response::validate(payload) {
+        return Err(ResponseError::Malformed);
+    }
+
+    // safe: the tag and every enum inside the payload were checked above
+    Ok(T::enum_into_response(unsafe { union.assume_init() }))
+}
+
 pub struct BotChannel {
     bkgfd: tempfile::NamedTempFile,
     mmap: MmapMut,
@@ -200,7 +420,7 @@ impl BotChannel {
         Ok(ret)
     }
 
-    pub fn backing_file_path<'a>(&'a self) -> &'a Path {
+    pub fn backing_file_path(&self) -> &Path {
         self.bkgfd.path()
     }
 
@@ -227,35 +447,11 @@ impl BotChannel {
         time::timeout(timeout, poll(
             sync, 
             EngineStatus::Busy as u8
-        )).await.map_err(|e| {
+        )).await.inspect_err(|_| {
             sync.store(EngineStatus::Busy as u8, Ordering::Release);
-            e
         })?;
 
-        let addr = ptr as usize;
-        let len = self.mmap.len();
-        let align = align_of::<Shm>();
-
-        if len != size_of::<Shm>() {
-            return Err(ResponseError::SizeMismatch {
-                expected: size_of::<Shm>(),
-                actual: len,
-            });
-        }
-        if addr % align != 0 {
-            return Err(ResponseError::AlignmentError {
-                address: addr,
-                alignment: align,
-            });
-        }
-
-        let discriminant = self.mmap[offset_of!(Shm, protocol)];
-        if discriminant != T::response_discriminant() {
-            return Err(ResponseError::Malformed);
-        }
-
-        let union = unsafe { &*(ptr.add(offset_of!(Shm, protocol)) as *const ProtocolUnion) };
-        Ok(T::enum_into_response(union.clone()))
+        read_response::<T>(&self.mmap)
     }
 }
 
@@ -284,10 +480,9 @@ impl EngineChannel {
         })
     }
 
-    pub async fn handle_msg(&self, strategy: &Strategy) {
-        let sync = deref_sync(&self.mmap);
+    pub async fn handle_msg(&mut self, strategy: &Strategy) {
         poll( // TODO handle engine finish
-            sync, 
+            deref_sync(&self.mmap), 
             EngineStatus::Ready as u8
         ).await;
 
@@ -295,8 +490,344 @@ impl EngineChannel {
         let msg = unsafe { &mut* (self.mmap.as_ptr().add(offset_of!(Shm, protocol)) as *mut ProtocolUnion) };
         let response = strategy.handle_msg(msg);
         *msg = response;
+        seal_response(&mut self.mmap);
+
+        deref_sync(&self.mmap).store(EngineStatus::Busy as u8, Ordering::Release);
+    }
+}
+
+
+/// the engine's end of the connection to one bot
+#[allow(async_fn_in_trait)]
+pub trait Transport {
+    /// passed to the bot as its only argument, see `EngineConnection::connect`
+    fn endpoint(&self) -> String;
+
+    async fn msg<T: Protocol>(&mut self, msg: &T::Msg, timeout: Duration) -> ResponseResult<T::Response>
+        where <T as Protocol>::Msg : Clone;
+}
 
-        sync.store(EngineStatus::Busy as u8, Ordering::Release);
+impl Transport for BotChannel {
+    fn endpoint(&self) -> String {
+        self.backing_file_path().display().to_string()
+    }
+
+    async fn msg<T: Protocol>(&mut self, msg: &T::Msg, timeout: Duration) -> ResponseResult<T::Response>
+        where <T as Protocol>::Msg : Clone
+    {
+        BotChannel::msg::<T>(self, msg, timeout).await
     }
 }
 
+pub const TCP_ENDPOINT_PREFIX: &str = "tcp://";
+
+/// how long a new connection has to present its token before the engine drops it and listens on
+pub const TCP_HELLO_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// frames larger than this are rejected, so a bot can't make the engine allocate without bound
+pub const TCP_MAX_FRAME: usize = 1 << 20;
+
+/// a tcp frame is a little endian u32 payload length, a u32 sequence number and then the json
+/// encoded ProtocolUnion. responses echo the sequence number of the message they answer
+struct FramedStream {
+    stream: TcpStream,
+    buf: Vec<u8>,
+}
+
+impl FramedStream {
+    fn new(stream: TcpStream) -> io::Result<Self> {
+        stream.set_nodelay(true)?;
+        Ok(Self { stream, buf: vec![] })
+    }
+
+    async fn write_frame(&mut self, seq: u32, msg: &ProtocolUnion) -> io::Result<()> {
+        self.write_payload(seq, &serde_json::to_vec(msg)?).await
+    }
+
+    async fn write_payload(&mut self, seq: u32, payload: &[u8]) -> io::Result<()> {
+        let mut frame = Vec::with_capacity(8 + payload.len());
+        frame.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        frame.extend_from_slice(&seq.to_le_bytes());
+        frame.extend_from_slice(payload);
+        self.stream.write_all(&frame).await
+    }
+
+    /// cancel safe: partially received frames stay buffered for the next call
+    async fn read_frame(&mut self) -> io::Result<(u32, Vec<u8>)> {
+        loop {
+            if self.buf.len() >= 8 {
+                let len = u32::from_le_bytes(self.buf[0..4].try_into().unwrap()) as usize;
+                if len > TCP_MAX_FRAME {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, format!("frame of {} bytes", len)));
+                }
+                if self.buf.len() >= 8 + len {
+                    let seq = u32::from_le_bytes(self.buf[4..8].try_into().unwrap());
+                    let payload = self.buf[8..8 + len].to_vec();
+                    self.buf.drain(..8 + len);
+                    return Ok((seq, payload));
+                }
+            }
+            if self.stream.read_buf(&mut self.buf).await? == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+        }
+    }
+}
+
+/// a tcp alternative to BotChannel for bots that can't share a file with the engine. the bot
+/// connects back to the listener on its first message, and its first frame must carry the
+/// random token from its endpoint. anyone else who reaches the port is dropped
+pub struct TcpBotChannel {
+    listener: TcpListener,
+    token: String,
+    stream: Option<FramedStream>,
+    seq: u32,
+    /// a frame write was cut off by a timeout, so the stream is out of sync for good
+    torn: bool,
+}
+
+impl TcpBotChannel {
+    pub async fn bind(host: IpAddr) -> anyhow::Result<Self> {
+        let listener = TcpListener::bind(SocketAddr::new(host, 0))
+            .await
+            .with_context(|| format!("unable to listen for bot on {}", host))?;
+        let token = format!("{:032x}", rand::random::<u128>());
+        Ok(Self { listener, token, stream: None, seq: 0, torn: false })
+    }
+
+    async fn accept(&mut self) -> io::Result<FramedStream> {
+        loop {
+            let mut stream = FramedStream::new(self.listener.accept().await?.0)?;
+            match time::timeout(TCP_HELLO_TIMEOUT, stream.read_frame()).await {
+                Ok(Ok((_, token))) if token == self.token.as_bytes() => return Ok(stream),
+                _ => continue,
+            }
+        }
+    }
+
+    async fn exchange<T: Protocol>(&mut self, msg: ProtocolUnion) -> ResponseResult<T::Response> {
+        let stream = match &mut self.stream {
+            Some(stream) => stream,
+            None => {
+                let stream = self.accept().await?;
+                self.stream.insert(stream)
+            }
+        };
+        self.seq = self.seq.wrapping_add(1);
+        self.torn = true;
+        stream.write_frame(self.seq, &msg).await?;
+        self.torn = false;
+        loop {
+            let (seq, payload) = stream.read_frame().await?;
+            // a late answer to a message that already timed out
+            if seq != self.seq {
+                continue;
+            }
+            let response: ProtocolUnion = serde_json::from_slice(&payload)
+                .map_err(|_| ResponseError::Malformed)?;
+            if response.discriminant() != T::response_discriminant() {
+                return Err(ResponseError::WrongProtocol {
+                    expected: describe_discriminant(T::response_discriminant()),
+                    actual: describe_discriminant(response.discriminant()),
+                });
+            }
+            return Ok(T::enum_into_response(response));
+        }
+    }
+}
+
+impl Transport for TcpBotChannel {
+    /// `tcp://<addr>/<token>`
+    fn endpoint(&self) -> String {
+        match self.listener.local_addr() {
+            Ok(addr) => format!("{}{}/{}", TCP_ENDPOINT_PREFIX, addr, self.token),
+            Err(_) => TCP_ENDPOINT_PREFIX.to_string(),
+        }
+    }
+
+    /// a message whose frame couldn't be written in time fails with a connection error, as does
+    /// every message after it
+    async fn msg<T: Protocol>(&mut self, msg: &T::Msg, timeout: Duration) -> ResponseResult<T::Response>
+        where <T as Protocol>::Msg : Clone
+    {
+        let torn = || ResponseError::Connection(io::Error::new(
+            io::ErrorKind::TimedOut,
+            "timed out writing a frame, the stream is out of sync",
+        ));
+        if self.torn {
+            return Err(torn());
+        }
+        match time::timeout(timeout, self.exchange::<T>(T::msg_into_enum(msg.clone()))).await {
+            Err(_) if self.torn => {
+                self.stream = None;
+                Err(torn())
+            }
+            result => result?,
+        }
+    }
+}
+
+/// the bot's end of a tcp connection to the engine
+pub struct TcpEngineChannel {
+    stream: FramedStream,
+}
+
+impl TcpEngineChannel {
+    /// endpoint is `<addr>/<token>`, as passed by the engine without the tcp:// prefix
+    pub async fn connect(endpoint: &str) -> anyhow::Result<Self> {
+        let (addr, token) = endpoint.rsplit_once('/').context("tcp endpoint has no token")?;
+        let stream = TcpStream::connect(addr)
+            .await
+            .with_context(|| format!("unable to connect to engine at {}", addr))?;
+        let mut stream = FramedStream::new(stream)?;
+        stream.write_payload(0, token.as_bytes()).await?;
+        Ok(Self { stream })
+    }
+
+    pub async fn handle_msg(&mut self, strategy: &Strategy) -> io::Result<()> {
+        let (seq, payload) = self.stream.read_frame().await?;
+        let msg: ProtocolUnion = serde_json::from_slice(&payload)?;
+        let response = strategy.handle_msg(&msg);
+        self.stream.write_frame(seq, &response).await
+    }
+}
+
+/// the bot's end of either transport, picked from the endpoint the engine passes
+pub enum EngineConnection {
+    Shm(EngineChannel),
+    Tcp(TcpEngineChannel),
+}
+
+impl EngineConnection {
+    pub async fn connect(endpoint: &str) -> anyhow::Result<Self> {
+        match endpoint.strip_prefix(TCP_ENDPOINT_PREFIX) {
+            Some(addr) => Ok(Self::Tcp(TcpEngineChannel::connect(addr).await?)),
+            None => Ok(Self::Shm(EngineChannel::from_path(endpoint)?)),
+        }
+    }
+
+    /// answers one message from the engine. fails once a tcp engine disconnects
+    pub async fn handle_msg(&mut self, strategy: &Strategy) -> io::Result<()> {
+        match self {
+            Self::Shm(channel) => {
+                channel.handle_msg(strategy).await;
+                Ok(())
+            }
+            Self::Tcp(channel) => channel.handle_msg(strategy).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn protocol_table_lists_every_protocol() {
+        let names: Vec<_> = PROTOCOLS.iter().map(|p| p.name).collect();
+        assert_eq!(names, ["Handshake", "Reset", "Tick"]);
+
+        let table = protocol_table();
+        for (id, p) in PROTOCOLS.iter().enumerate() {
+            let line = format!(
+                "{} (id {}): msg {} = {} ({} bytes), response {} = {} ({} bytes)",
+                p.name, id, id * 2, p.msg, p.msg_size, id * 2 + 1, p.response, p.response_size
+            );
+            assert!(table.lines().any(|l| l == line), "missing {line:?} in\n{table}");
+        }
+    }
+
+    #[tokio::test]
+    async fn flipped_shm_byte_fails_the_checksum() {
+        // a fresh region reads as a zeroed handshake message
+        let channel = BotChannel::new().unwrap();
+        let mut bot = EngineChannel::from_path(channel.backing_file_path()).unwrap();
+        deref_sync(&channel.mmap).store(EngineStatus::Ready as u8, Ordering::Release);
+        bot.handle_msg(&handshake_only()).await;
+        assert_eq!(read_response::<HandshakeProtocol>(&channel.mmap).unwrap().magic, HANDSHAKE_BOT);
+
+        let mut region = MmapMut::map_anon(SHM_SIZE).unwrap();
+        region.copy_from_slice(&channel.mmap);
+        region[offset_of!(Shm, protocol) + PROTOCOL_PAYLOAD_OFFSET] ^= 1;
+        assert!(matches!(read_response::<HandshakeProtocol>(&region), Err(ResponseError::Malformed)));
+    }
+
+    #[tokio::test]
+    async fn wrong_response_names_both_protocols() {
+        let channel = BotChannel::new().unwrap();
+        let mut bot = EngineChannel::from_path(channel.backing_file_path()).unwrap();
+        deref_sync(&channel.mmap).store(EngineStatus::Ready as u8, Ordering::Release);
+        bot.handle_msg(&handshake_only()).await;
+
+        // the engine was waiting on a reset, but the bot answered the handshake
+        let error = read_response::<ResetProtocol>(&channel.mmap).unwrap_err();
+        assert!(matches!(error, ResponseError::WrongProtocol { .. }));
+        assert_eq!(error.to_string(), "wrong protocol (expected Reset response, got Handshake response)");
+    }
+
+    fn handshake() -> HandshakeMsg {
+        HandshakeMsg { team: Team::A, version: PROTOCOL_VERSION, config: GameConfig::default() }
+    }
+
+    fn handshake_only() -> Strategy {
+        Strategy {
+            on_handshake: Box::new(|_| HandshakeResponse::CURRENT),
+            on_reset: Box::new(|_| panic!("unexpected reset")),
+            on_tick: Box::new(|_| panic!("unexpected tick")),
+        }
+    }
+
+    #[tokio::test]
+    async fn tcp_drops_connections_without_the_token() {
+        let mut channel = TcpBotChannel::bind(IpAddr::V4(std::net::Ipv4Addr::LOCALHOST)).await.unwrap();
+        let endpoint = channel.endpoint();
+        let (addr, _) = endpoint.strip_prefix(TCP_ENDPOINT_PREFIX).unwrap().rsplit_once('/').unwrap();
+
+        let stranger = async {
+            let mut stream = FramedStream::new(TcpStream::connect(addr).await.unwrap()).unwrap();
+            stream.write_payload(0, b"guess").await.unwrap();
+            // the engine hangs up instead of sending it anything
+            assert!(stream.read_frame().await.is_err());
+        };
+        let bot = async {
+            let mut bot = EngineConnection::connect(&endpoint).await.unwrap();
+            bot.handle_msg(&handshake_only()).await.unwrap();
+        };
+        let engine = async {
+            let response = channel.msg::<HandshakeProtocol>(&handshake(), Duration::from_secs(5)).await;
+            assert_eq!(response.unwrap().magic, HANDSHAKE_BOT);
+        };
+        tokio::join!(async { stranger.await; bot.await }, engine);
+    }
+
+    #[tokio::test]
+    async fn tcp_torn_write_is_fatal() {
+        let mut channel = TcpBotChannel::bind(IpAddr::V4(std::net::Ipv4Addr::LOCALHOST)).await.unwrap();
+        let endpoint = channel.endpoint();
+        let (addr, token) = endpoint.strip_prefix(TCP_ENDPOINT_PREFIX).unwrap().rsplit_once('/').unwrap();
+
+        // a bot that connects and then never reads, so the engine's writes eventually block
+        let socket = tokio::net::TcpSocket::new_v4().unwrap();
+        socket.set_recv_buffer_size(4096).unwrap();
+        let mut bot = FramedStream::new(socket.connect(addr.parse().unwrap()).await.unwrap()).unwrap();
+        bot.write_payload(0, token.as_bytes()).await.unwrap();
+
+        let msg = handshake();
+        let mut sent = 0;
+        let error = loop {
+            match channel.msg::<HandshakeProtocol>(&msg, Duration::from_millis(1)).await {
+                Err(ResponseError::Timeout(_)) => sent += 1,
+                Err(e @ ResponseError::Connection(_)) => break e,
+                _ => panic!("unexpected response"),
+            }
+            assert!(sent < 100_000, "the writes never blocked");
+        };
+        assert!(error.to_string().contains("out of sync"), "{error}");
+        assert!(channel.stream.is_none());
+        // nothing is sent on a stream cut off mid frame
+        assert!(matches!(
+            channel.msg::<HandshakeProtocol>(&msg, Duration::from_secs(1)).await,
+            Err(ResponseError::Connection(_))
+        ));
+    }
+}
@@ -2,4 +2,6 @@ pub mod game;
 pub mod ipc;
 pub mod args;
 pub mod engine;
+pub mod snapshot;
+pub mod replay;
 
@@ -0,0 +1,130 @@
+//! Recorded matches that can be re-emitted without running the bots.
+//!
+//! A replay file starts with a version byte and the config json on its own line, so tools can
+//! read the config without understanding the rest. Every later gamelog line follows as
+//! `<kind>\t<line>`, unformatted, so a replay can be printed tagged or untagged.
+//!
+//! States are kept as the same json the gamelog carries, so a replay is about as large as the
+//! gamelog. There is no binary encoding of states yet.
+
+use anyhow::{bail, Context, Result};
+use std::fs::File;
+use std::io::{ self, BufRead, BufReader, BufWriter, Read, Write };
+use std::path::Path;
+use tokio::sync::mpsc;
+use crate::{
+    args::*,
+    game::stats::MatchResult,
+};
+
+pub const REPLAY_VERSION: u8 = 1;
+
+pub struct ReplayWriter {
+    out: BufWriter<File>,
+    header_done: bool,
+}
+
+impl ReplayWriter {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        let mut out = BufWriter::new(File::create(path)?);
+        out.write_all(&[REPLAY_VERSION])?;
+        Ok(Self { out, header_done: false })
+    }
+
+    pub fn write(&mut self, msg: &Message) -> io::Result<()> {
+        if msg.source != OutputSource::Gamelog {
+            return Ok(());
+        }
+        if !self.header_done {
+            self.header_done = true;
+            if msg.kind == LineKind::Config {
+                return writeln!(self.out, "{}", msg.msg);
+            }
+            // the match failed before sending its config
+            writeln!(self.out, "null")?;
+        }
+        writeln!(self.out, "{}\t{}", msg.kind.tag(), msg.msg)
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// re-sends a recorded gamelog to tx, returning the recorded result if the match finished
+pub fn replay(path: &Path, tx: &mpsc::UnboundedSender<Message>) -> Result<Option<MatchResult>> {
+    let mut reader = BufReader::new(
+        File::open(path).with_context(|| format!("failed to open replay {}", path.display()))?
+    );
+
+    let mut version = [0u8];
+    reader.read_exact(&mut version).context("replay is empty")?;
+    if version[0] != REPLAY_VERSION {
+        bail!("unsupported replay version {} (expected {})", version[0], REPLAY_VERSION);
+    }
+
+    let mut lines = reader.lines();
+    let config = lines.next().context("replay is missing its config")??;
+    if config != "null" {
+        send!(tx, OutputSource::Gamelog, LineKind::Config; "{}", config);
+    }
+
+    let mut result = None;
+    for line in lines {
+        let line = line?;
+        let (tag, msg) = line.split_once('\t').context("malformed replay line")?;
+        let kind = LineKind::from_tag(tag).unwrap_or(LineKind::Event);
+        if kind == LineKind::Result {
+            result = Some(serde_json::from_str(msg).context("malformed replay result")?);
+        }
+        send!(tx, OutputSource::Gamelog, kind; "{}", msg);
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{ config::GameConfig, sim::*, state::{ GameState, TeamPair } };
+
+    fn message(source: OutputSource, kind: LineKind, msg: String) -> Message {
+        Message { msg, source, kind }
+    }
+
+    #[test]
+    fn replay_matches_the_recorded_gamelog() {
+        let conf = GameConfig::default();
+        let mut state = GameState::new(&conf);
+        let mut rng = match_rng(1);
+        let mut sent = vec![
+            message(OutputSource::Gamelog, LineKind::Config, serde_json::to_string(&conf).unwrap()),
+            message(OutputSource::Gamelog, LineKind::Event, "# seed: 1".to_string()),
+        ];
+        for _ in 0..5 {
+            simulate_tick(&mut state, &conf, Default::default(), &mut rng);
+            sent.push(message(OutputSource::Gamelog, LineKind::State, serde_json::to_string(&state).unwrap()));
+            // bot output isn't part of the replay
+            sent.push(message(OutputSource::BotA, LineKind::Event, "#[bot]: thinking".to_string()));
+        }
+        let result = MatchResult::cancelled("id", 1, &TeamPair::new(2, 1));
+        sent.push(message(OutputSource::Gamelog, LineKind::Result, serde_json::to_string(&result).unwrap()));
+
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let mut writer = ReplayWriter::create(file.path()).unwrap();
+        for msg in &sent {
+            writer.write(msg).unwrap();
+        }
+        writer.flush().unwrap();
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let replayed_result = replay(file.path(), &tx).unwrap().expect("no recorded result");
+        assert!(replayed_result == result);
+
+        let replayed = std::iter::from_fn(|| rx.try_recv().ok()).map(|m| (m.source, m.kind, m.msg));
+        let expected = sent
+            .into_iter()
+            .filter(|m| m.source == OutputSource::Gamelog)
+            .map(|m| (m.source, m.kind, m.msg));
+        assert!(replayed.eq(expected));
+    }
+}
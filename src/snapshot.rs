@@ -0,0 +1,145 @@
+use std::{ collections::VecDeque, fmt::Write };
+use crate::game::{
+    config::GameConfig,
+    state::{ GameState, PlayerArray, Team, TeamPair },
+    stats::TeamInfo,
+    util::Vec2,
+};
+
+/// the last few player and ball positions, drawn as motion trails
+pub struct TrailBuffer {
+    len: usize,
+    frames: VecDeque<(PlayerArray<Vec2>, Vec2)>,
+}
+
+impl TrailBuffer {
+    pub fn new(len: usize) -> Self {
+        Self { len, frames: VecDeque::with_capacity(len) }
+    }
+
+    pub fn push(&mut self, state: &GameState) {
+        if self.len == 0 {
+            return;
+        }
+        if self.frames.len() == self.len {
+            self.frames.pop_front();
+        }
+        self.frames.push_back((state.players.each_ref().map(|p| p.pos), state.ball.pos));
+    }
+
+    fn write_polyline(svg: &mut String, points: impl Iterator<Item = Vec2>, color: &str) {
+        let points = points.map(|p| format!("{},{}", p.x, p.y)).collect::<Vec<_>>().join(" ");
+        let _ = writeln!(
+            svg,
+            r#"<polyline class="trail" points="{points}" fill="none" stroke="{color}" stroke-opacity="0.5"/>"#,
+        );
+    }
+
+    fn render(&self, svg: &mut String, state: &GameState, teams: &TeamPair<TeamInfo>) {
+        if self.frames.len() < 2 {
+            return;
+        }
+        for player in &state.players {
+            let team = state.player_team(player.id).unwrap();
+            let points = self.frames.iter().map(|(players, _)| players[player.id as usize]);
+            Self::write_polyline(svg, points, &teams[team].color);
+        }
+        Self::write_polyline(svg, self.frames.iter().map(|(_, ball)| *ball), "#fff");
+    }
+}
+
+/// render the field, goals, players and ball as an svg document, with trails if any were recorded
+pub fn render_svg(state: &GameState, conf: &GameConfig, teams: &TeamPair<TeamInfo>, trails: &TrailBuffer) -> String {
+    let (w, h) = (conf.field.width as f32, conf.field.height as f32);
+    let goal_h = conf.goal.current_height(conf, state.tick) as f32;
+    let goal_t = conf.goal.thickness as f32;
+    let center = conf.field.center();
+
+    let mut svg = String::new();
+    let _ = writeln!(svg, r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}">"#);
+    let _ = writeln!(svg, r##"<rect x="0" y="0" width="{w}" height="{h}" fill="#2a6"/>"##);
+    let _ = writeln!(svg, r##"<line x1="{x}" y1="0" x2="{x}" y2="{h}" stroke="#fff"/>"##, x = center.x);
+
+    for (x, team) in [(0.0, Team::A), (w - goal_t, Team::B)] {
+        let _ = writeln!(
+            svg,
+            r#"<rect x="{x}" y="{y}" width="{goal_t}" height="{goal_h}" fill="{color}"/>"#,
+            y = center.y - goal_h * 0.5,
+            color = teams[team].color,
+        );
+    }
+
+    trails.render(&mut svg, state, teams);
+
+    for player in &state.players {
+        let team = state.player_team(player.id).unwrap();
+        let _ = writeln!(
+            svg,
+            r#"<circle class="player" cx="{x}" cy="{y}" r="{r}" fill="{color}"/>"#,
+            x = player.pos.x,
+            y = player.pos.y,
+            r = player.radius,
+            color = teams[team].color,
+        );
+        let _ = writeln!(
+            svg,
+            r##"<text x="{x}" y="{y}" font-size="10" text-anchor="middle" dominant-baseline="central" fill="#fff">{id}</text>"##,
+            x = player.pos.x,
+            y = player.pos.y,
+            id = player.id,
+        );
+    }
+
+    let _ = writeln!(
+        svg,
+        r##"<circle class="ball" cx="{x}" cy="{y}" r="{r}" fill="#fff"/>"##,
+        x = state.ball.pos.x,
+        y = state.ball.pos.y,
+        r = state.ball.radius,
+    );
+    let _ = writeln!(
+        svg,
+        r##"<text x="{x}" y="20" font-size="16" text-anchor="middle" fill="#fff">{name_a}: {a} {name_b}: {b}</text>"##,
+        x = center.x,
+        name_a = escape(&teams.a.name),
+        name_b = escape(&teams.b.name),
+        a = state.score.a,
+        b = state.score.b,
+    );
+    svg.push_str("</svg>\n");
+    svg
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::config::NUM_PLAYERS;
+
+    #[test]
+    fn trails_cover_the_last_frames() {
+        let conf = GameConfig::default();
+        let mut state = GameState::new(&conf);
+        let mut trails = TrailBuffer::new(5);
+        for tick in 0..8 {
+            state.players.iter_mut().for_each(|p| p.pos = Vec2::new(100.0 + tick as f32, 100.0 + p.id as f32 * 50.0));
+            state.ball.pos = Vec2::new(300.0, 100.0 + tick as f32);
+            trails.push(&state);
+        }
+        let svg = render_svg(&state, &conf, &TeamPair::default(), &trails);
+        let lines: Vec<_> = svg.lines().filter(|line| line.contains(r#"class="trail""#)).collect();
+        // one per player and one for the ball, each over the last five frames only
+        assert_eq!(lines.len(), NUM_PLAYERS as usize * 2 + 1);
+        for line in &lines {
+            let points = line.split(r#"points=""#).nth(1).unwrap().split('"').next().unwrap();
+            assert_eq!(points.split(' ').count(), 5, "{line}");
+        }
+        assert!(lines[0].contains("103,100 104,100 105,100 106,100 107,100"), "{}", lines[0]);
+
+        let svg = render_svg(&state, &conf, &TeamPair::default(), &TrailBuffer::new(0));
+        assert!(!svg.contains(r#"class="trail""#));
+    }
+}
@@ -0,0 +1,973 @@
+//! End to end: the engine against the example bot on both sides, over real shared memory or tcp.
+
+use clap::Parser;
+use mm_engine::{
+    args::ArgConfig,
+    engine::{ run_match, CancelToken },
+    game::{
+        config::{ GameConfig, TimeoutPolicy, NUM_PLAYERS },
+        sim::*,
+        state::{ mirror_pos, GameState, Mirror, PlayerAction, ResetCause, StateOption, Team, TeamPair },
+        stats::{ DecidedBy, MatchResult, SeriesTally },
+        util::{ Fnv1a, Vec2 },
+    },
+    ipc::{ EngineConnection, HandshakeResponse, Strategy, PROTOCOL_VERSION },
+};
+use std::{ os::unix::fs::PermissionsExt, path::Path, process::Command, time::{ Duration, Instant } };
+use tokio::sync::mpsc;
+
+// a script in `dir` that logs its pid and then execs `exe`, so a leak check can look for exactly
+// the bots this match started and not those of tests running alongside
+fn tracked_bot(dir: &Path, exe: &Path) -> std::path::PathBuf {
+    let bot = dir.join("tracked-bot");
+    let pids = dir.join("pids");
+    std::fs::write(&bot, format!("#!/bin/sh\necho $$ >> {}\nexec {} \"$@\"\n", pids.display(), exe.display())).unwrap();
+    std::fs::set_permissions(&bot, std::fs::Permissions::from_mode(0o755)).unwrap();
+    bot
+}
+
+// pids logged by the tracked bots in `dir` that are still alive, zombies count as gone
+fn survivors(dir: &Path) -> Vec<u32> {
+    let pids: Vec<u32> = std::fs::read_to_string(dir.join("pids"))
+        .unwrap()
+        .lines()
+        .map(|pid| pid.parse().unwrap())
+        .collect();
+    assert_eq!(pids.len(), 2, "expected both bots to start");
+    pids.into_iter()
+        .filter(|pid| {
+            std::fs::read_to_string(format!("/proc/{pid}/stat"))
+                .is_ok_and(|stat| stat.rsplit(')').next().is_some_and(|rest| !rest.trim_start().starts_with('Z')))
+        })
+        .collect()
+}
+
+fn result_of(stdout: &str) -> MatchResult {
+    stdout
+        .lines()
+        .find_map(|line| line.strip_prefix("# result: "))
+        .map(|json| serde_json::from_str(json).unwrap())
+        .expect("no result line")
+}
+
+// runs the example bot against itself and returns the engine's stdout
+fn run_engine(config: &Path, args: &[&str]) -> String {
+    let bot = env!("CARGO_BIN_EXE_bot");
+    let output = Command::new(env!("CARGO_BIN_EXE_mm-engine"))
+        .args([bot, bot])
+        .arg("--config")
+        .arg(config)
+        .args(["--print", "g"])
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "engine failed: {}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+#[ignore = "spawns the engine and two bot processes, run with --ignored"]
+fn short_match_against_example_bot() {
+    let dir = tempfile::tempdir().unwrap();
+    let bot = &tracked_bot(dir.path(), Path::new(env!("CARGO_BIN_EXE_bot")));
+
+    let conf = GameConfig {
+        max_ticks: 200,
+        endgame_ticks: 0,
+        ..Default::default()
+    };
+    let config = tempfile::NamedTempFile::new().unwrap();
+    std::fs::write(config.path(), serde_json::to_string(&conf).unwrap()).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_mm-engine"))
+        .arg(bot)
+        .arg(bot)
+        .arg("--config")
+        .arg(config.path())
+        .args(["--seed", "1", "--print", "g"])
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(output.status.success(), "engine failed: {}", String::from_utf8_lossy(&output.stderr));
+
+    let states = stdout.lines().filter(|line| line.starts_with('{') && line.contains("\"tick\"")).count();
+    assert_eq!(states, conf.max_ticks as usize);
+
+    let result = result_of(&stdout);
+    assert!(!result.cancelled);
+    assert_eq!(result.seed, 1);
+    let expected = match result.score.a.cmp(&result.score.b) {
+        std::cmp::Ordering::Greater => Some(Team::A),
+        std::cmp::Ordering::Less => Some(Team::B),
+        std::cmp::Ordering::Equal => None,
+    };
+    assert!(result.winner == expected);
+
+    assert!(survivors(dir.path()).is_empty(), "bot processes outlived the match");
+}
+
+fn config_file(conf: &GameConfig) -> tempfile::NamedTempFile {
+    let file = tempfile::NamedTempFile::new().unwrap();
+    std::fs::write(file.path(), serde_json::to_string(conf).unwrap()).unwrap();
+    file
+}
+
+// cancels `args` after `after` and checks the match comes back promptly as cancelled
+async fn cancel_after(args: &[&std::ffi::OsStr], after: Duration) -> MatchResult {
+    let args = ArgConfig::parse_from(args);
+    let (tx, _rx) = mpsc::unbounded_channel();
+    let cancel = CancelToken::default();
+    let canceller = {
+        let cancel = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(after).await;
+            cancel.cancel();
+        })
+    };
+
+    let start = Instant::now();
+    let result = run_match(&args, &tx, &cancel).await.unwrap();
+    canceller.await.unwrap();
+    assert!(start.elapsed() < after + Duration::from_secs(2), "cancel took {:?}", start.elapsed() - after);
+    result
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "spawns the engine and two bot processes, run with --ignored"]
+async fn cancel_mid_match() {
+    let dir = tempfile::tempdir().unwrap();
+    let bot = &tracked_bot(dir.path(), Path::new(env!("CARGO_BIN_EXE_bot")));
+    let config = config_file(&GameConfig { max_ticks: 1_000_000, ..Default::default() });
+
+    let result = cancel_after(
+        &["mm-engine".as_ref(), bot.as_os_str(), bot.as_os_str(), "--config".as_ref(), config.path().as_os_str()],
+        Duration::from_millis(300),
+    ).await;
+    assert!(result.cancelled);
+    assert!(result.winner.is_none());
+    assert!(survivors(dir.path()).is_empty(), "bot processes outlived the match");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "spawns the engine and two bot processes, run with --ignored"]
+async fn cancel_during_handshake() {
+    // never answers, so the match sits in the handshake until it times out
+    let dir = tempfile::tempdir().unwrap();
+    let silent = dir.path().join("silent-bot");
+    std::fs::write(&silent, "#!/bin/sh\nwhile true; do sleep 1; done\n").unwrap();
+    std::fs::set_permissions(&silent, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let bot = tracked_bot(dir.path(), &silent);
+
+    let result = cancel_after(
+        &["mm-engine".as_ref(), bot.as_os_str(), bot.as_os_str(), "--handshake-timeout-secs".as_ref(), "60".as_ref()],
+        Duration::from_millis(300),
+    ).await;
+    assert!(result.cancelled);
+    assert!(survivors(dir.path()).is_empty(), "bot processes outlived the match");
+}
+
+#[test]
+#[ignore = "spawns the engine and two bot processes, run with --ignored"]
+fn logged_seed_reproduces_the_match() {
+    let config = config_file(&GameConfig { max_ticks: 200, endgame_ticks: 0, ..Default::default() });
+
+    let first = run_engine(config.path(), &[]);
+    let header: serde_json::Value = serde_json::from_str(first.lines().next().unwrap()).unwrap();
+    let seed = header["seed"].as_u64().expect("config header has no seed");
+    let first = result_of(&first);
+    assert_eq!(first.seed, seed);
+
+    let rerun = result_of(&run_engine(config.path(), &["--seed", &seed.to_string()]));
+    assert_eq!(rerun.checksum, first.checksum);
+    assert_eq!(rerun.score, first.score);
+}
+
+#[test]
+#[ignore = "spawns the engine and two bot processes, run with --ignored"]
+fn same_seed_gives_identical_states() {
+    let config = config_file(&GameConfig { max_ticks: 300, endgame_ticks: 0, ..Default::default() });
+    let states = |stdout: String| -> Vec<String> {
+        stdout.lines().filter(|line| line.starts_with('{') && line.contains("\"tick\"")).map(String::from).collect()
+    };
+
+    let (first, rerun) = (run_engine(config.path(), &["--seed", "7"]), run_engine(config.path(), &["--seed", "7"]));
+    let checksum = result_of(&first).checksum;
+    assert_eq!(result_of(&rerun).checksum, checksum);
+    let first = states(first);
+    assert_eq!(first.len(), 300);
+    assert_eq!(states(rerun), first);
+
+    // another seed plays a different match and the checksum shows it
+    assert_ne!(result_of(&run_engine(config.path(), &["--seed", "8"])).checksum, checksum);
+}
+
+#[test]
+#[ignore = "spawns the engine and two bot processes, run with --ignored"]
+fn started_score_carries_into_overtime() {
+    let config = config_file(&GameConfig { max_ticks: 20, endgame_ticks: 30, ..Default::default() });
+    let states = |stdout: &str| -> Vec<serde_json::Value> {
+        stdout
+            .lines()
+            .filter(|line| line.starts_with('{') && line.contains("\"tick\""))
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    };
+
+    // a tie at full time plays on into the endgame window
+    let tied = run_engine(config.path(), &["--seed", "3", "--start-score", "2:2"]);
+    let frames = states(&tied);
+    assert_eq!(frames[0]["score"], serde_json::json!({ "a": 2, "b": 2 }));
+    assert!(frames.len() > 20, "{} ticks", frames.len());
+    let result = result_of(&tied);
+    assert!(result.score.a >= 2 && result.score.b >= 2);
+
+    let ahead = run_engine(config.path(), &["--seed", "3", "--start-score", "3:2"]);
+    assert_eq!(states(&ahead).len(), 20);
+    assert_eq!(result_of(&ahead).winner, Some(Team::A));
+}
+
+#[test]
+#[ignore = "spawns the engine and two bot processes, run with --ignored"]
+fn setup_failure_emits_a_fatal_record() {
+    let config = config_file(&GameConfig { max_ticks: 20, endgame_ticks: 0, ..Default::default() });
+    let dir = tempfile::tempdir().unwrap();
+    let missing = dir.path().join("missing-bot");
+    let output = Command::new(env!("CARGO_BIN_EXE_mm-engine"))
+        .arg(&missing)
+        .arg(env!("CARGO_BIN_EXE_bot"))
+        .arg("--config")
+        .arg(config.path())
+        .args(["--print", "g"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let fatal: serde_json::Value = stdout
+        .lines()
+        .find_map(|line| serde_json::from_str::<serde_json::Value>(line).ok().filter(|json| json.get("fatal").is_some()))
+        .expect("no fatal record");
+    assert!(fatal["fatal"].as_str().unwrap().contains("failed to spawn"), "{fatal}");
+    assert!(fatal["cause"].is_string(), "{fatal}");
+    assert!(!stdout.contains("# result: "), "{stdout}");
+}
+
+#[test]
+#[ignore = "spawns the engine and two bot processes, run with --ignored"]
+fn tagged_gamelog_lines_are_envelopes() {
+    let config = config_file(&GameConfig { max_ticks: 200, endgame_ticks: 0, ..Default::default() });
+    let dir = tempfile::tempdir().unwrap();
+    let gamelog = dir.path().join("gamelog.jsonl");
+    run_engine(config.path(), &["--tagged", "-o", &format!("g:{}", gamelog.display())]);
+
+    let lines: Vec<serde_json::Value> = std::fs::read_to_string(&gamelog)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap_or_else(|e| panic!("{e}: {line}")))
+        .collect();
+    let types: Vec<&str> = lines.iter().map(|line| line["type"].as_str().expect("untagged line")).collect();
+    assert!(lines.iter().all(|line| line.get("data").is_some()));
+    assert_eq!(types[0], "config");
+    assert_eq!(lines[0]["data"]["max_ticks"], 200);
+    assert_eq!(types.iter().filter(|kind| **kind == "state").count(), 200);
+    assert!(lines.iter().filter(|line| line["type"] == "state").all(|line| line["data"]["tick"].is_u64()));
+    assert!(types.contains(&"event") && types.contains(&"referee"), "{types:?}");
+    assert_eq!(types.iter().filter(|kind| **kind == "result").count(), 1);
+    assert!(types.iter().all(|kind| ["config", "state", "event", "referee", "result"].contains(kind)), "{types:?}");
+}
+
+// a script that prints a line and then runs the example bot
+fn chatty_bot(dir: &Path) -> std::path::PathBuf {
+    let bot = dir.join("bot");
+    std::fs::write(&bot, format!("#!/bin/sh\necho hello\necho oops >&2\nexec {} \"$@\"\n", env!("CARGO_BIN_EXE_bot"))).unwrap();
+    std::fs::set_permissions(&bot, std::fs::Permissions::from_mode(0o755)).unwrap();
+    bot
+}
+
+#[test]
+#[ignore = "spawns the engine and two bot processes, run with --ignored"]
+fn crashed_bot_forfeits() {
+    let config = config_file(&GameConfig { max_ticks: 1_000_000, ..Default::default() });
+    let dir = tempfile::tempdir().unwrap();
+    // the example bot, killed a few ticks into the match
+    let crashing = dir.path().join("crashing");
+    std::fs::write(&crashing, format!("#!/bin/sh
+(sleep 0.3; kill $$) &
+exec {} \"$@\"\n", env!("CARGO_BIN_EXE_bot"))).unwrap();
+    std::fs::set_permissions(&crashing, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let start = Instant::now();
+    let output = Command::new(env!("CARGO_BIN_EXE_mm-engine"))
+        .arg(&crashing)
+        .arg(env!("CARGO_BIN_EXE_bot"))
+        .arg("--config")
+        .arg(config.path())
+        .args(["--print", "g"])
+        .output()
+        .unwrap();
+    assert!(start.elapsed() < Duration::from_secs(5));
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.lines().any(|line| line.starts_with("# Bot A forfeited at tick ")), "{stdout}");
+    let result = result_of(&stdout);
+    assert_eq!(result.winner, Some(Team::B));
+    assert_eq!(result.decided_by, Some(DecidedBy::Forfeit));
+}
+
+#[test]
+#[ignore = "spawns the engine and two bot processes, run with --ignored"]
+fn interrupted_match_leaves_a_complete_gamelog() {
+    let config = config_file(&GameConfig { max_ticks: 1_000_000, ..Default::default() });
+    let dir = tempfile::tempdir().unwrap();
+    let gamelog = dir.path().join("gamelog.txt");
+    let bot = env!("CARGO_BIN_EXE_bot");
+    let engine = Command::new(env!("CARGO_BIN_EXE_mm-engine"))
+        .args([bot, bot])
+        .arg("--config")
+        .arg(config.path())
+        .args(["-o", &format!("g:{}", gamelog.display())])
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    std::thread::sleep(Duration::from_millis(500));
+    Command::new("kill").args(["-INT", &engine.id().to_string()]).status().unwrap();
+    let output = engine.wait_with_output().unwrap();
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("interrupted"));
+
+    let gamelog = std::fs::read_to_string(&gamelog).unwrap();
+    assert!(gamelog.ends_with('\n'));
+    // every line made it out whole, up to the result of the cancelled match
+    for line in gamelog.lines().filter(|line| !line.starts_with('#')) {
+        serde_json::from_str::<serde_json::Value>(line).unwrap_or_else(|e| panic!("{e}: {line}"));
+    }
+    assert!(result_of(&gamelog).cancelled);
+}
+
+#[test]
+#[ignore = "spawns the engine and two bot processes, run with --ignored"]
+fn team_names_tag_bot_output() {
+    let config = config_file(&GameConfig { max_ticks: 20, endgame_ticks: 0, ..Default::default() });
+    let dir = tempfile::tempdir().unwrap();
+    let bot = chatty_bot(dir.path());
+
+    let output = Command::new(env!("CARGO_BIN_EXE_mm-engine"))
+        .arg(&bot)
+        .arg(&bot)
+        .arg("--config")
+        .arg(config.path())
+        .args(["--name-a", "custom", "--print", "a,b,aerr,g"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    // both sides keep their tag apart even when the names match
+    assert!(stdout.lines().any(|line| line == "#[A:custom]: hello"), "{stdout}");
+    assert!(stdout.lines().any(|line| line == "#[B:bot]: hello"), "{stdout}");
+    assert!(stdout.lines().any(|line| line == "#[A:custom] ERR: oops"), "{stdout}");
+    let result = result_of(&stdout);
+    assert_eq!(result.teams.a.name, "custom");
+    assert_eq!(result.teams.b.name, "bot");
+}
+
+#[test]
+#[ignore = "spawns the engine and two bot processes, run with --ignored"]
+fn bot_stderr_goes_to_its_own_file() {
+    let config = config_file(&GameConfig { max_ticks: 20, endgame_ticks: 0, ..Default::default() });
+    let dir = tempfile::tempdir().unwrap();
+    let bot = chatty_bot(dir.path());
+    let err_log = dir.path().join("a-err.log");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_mm-engine"))
+        .arg(&bot)
+        .arg(&bot)
+        .arg("--config")
+        .arg(config.path())
+        .args(["--print", "a,g", "-o"])
+        .arg(format!("aerr:{}", err_log.display()))
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.lines().any(|line| line == "#[A:bot]: hello"), "{stdout}");
+    assert!(!stdout.contains("oops"), "{stdout}");
+
+    let err = std::fs::read_to_string(&err_log).unwrap();
+    assert_eq!(err.lines().collect::<Vec<_>>(), ["#[A:bot] ERR: oops"]);
+}
+
+#[test]
+#[ignore = "spawns the engine and two bot processes, run with --ignored"]
+fn flooding_bot_output_is_capped() {
+    let config = config_file(&GameConfig { max_ticks: 20, endgame_ticks: 0, ..Default::default() });
+    let dir = tempfile::tempdir().unwrap();
+    let bot = dir.path().join("bot");
+    std::fs::write(&bot, format!("#!/bin/sh\nyes flood | head -n 5000\nexec {} \"$@\"\n", env!("CARGO_BIN_EXE_bot"))).unwrap();
+    std::fs::set_permissions(&bot, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_mm-engine"))
+        .arg(&bot)
+        .arg(env!("CARGO_BIN_EXE_bot"))
+        .arg("--config")
+        .arg(config.path())
+        .args(["--print", "a,g", "--bot-output-limit", "1000"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let flood = stdout.lines().filter(|line| *line == "#[A:bot]: flood").count();
+    assert_eq!(flood, 1000 / "flood".len());
+    let notices: Vec<_> = stdout.lines().filter(|line| line.contains("output suppressed")).collect();
+    assert_eq!(notices, ["### [bot A:bot] output suppressed after 1000 bytes"]);
+}
+
+#[test]
+#[ignore = "spawns the engine and two bot processes, run with --ignored"]
+fn socket_client_gets_the_gamelog() {
+    let config = config_file(&GameConfig { max_ticks: 20, endgame_ticks: 0, ..Default::default() });
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("gamelog.sock");
+    let listener = std::os::unix::net::UnixListener::bind(&path).unwrap();
+    let client = std::thread::spawn(move || {
+        let mut gamelog = String::new();
+        std::io::Read::read_to_string(&mut listener.accept().unwrap().0, &mut gamelog).unwrap();
+        gamelog
+    });
+
+    let stdout = run_engine(config.path(), &["--socket", path.to_str().unwrap()]);
+    let gamelog = client.join().unwrap();
+    // the final score is printed after the gamelog ends
+    assert!(stdout.starts_with(&gamelog), "{gamelog}");
+    let config_line = gamelog.lines().next().unwrap();
+    assert_eq!(serde_json::from_str::<GameConfig>(config_line).unwrap().max_ticks, 20);
+    let frames = gamelog.lines().filter(|line| line.starts_with('{') && line.contains(r#""ball""#)).count();
+    assert!(frames >= 20, "{frames} frames");
+}
+
+#[test]
+#[ignore = "spawns the engine and two bot processes, run with --ignored"]
+fn snapshot_draws_every_player() {
+    let config = config_file(&GameConfig { max_ticks: 20, endgame_ticks: 0, ..Default::default() });
+    let dir = tempfile::tempdir().unwrap();
+    let snapshot = dir.path().join("final.svg");
+    run_engine(config.path(), &["--snapshot", snapshot.to_str().unwrap()]);
+
+    let svg = std::fs::read_to_string(&snapshot).unwrap();
+    assert!(svg.starts_with("<svg") && svg.ends_with("</svg>\n"));
+    assert_eq!(svg.matches(r#"class="player""#).count(), NUM_PLAYERS as usize * 2);
+    assert_eq!(svg.matches(r#"class="ball""#).count(), 1);
+}
+
+// a Debug formatted Duration such as 12.5ms, in milliseconds
+fn millis(debug: &str) -> f64 {
+    let units = [("ns", 1e-6), ("µs", 1e-3), ("ms", 1.0), ("s", 1e3)];
+    let (unit, scale) = units.iter().find(|(unit, _)| debug.ends_with(unit)).unwrap();
+    debug.trim_end_matches(unit).parse::<f64>().unwrap() * scale
+}
+
+type MakeStrategy = Box<dyn FnOnce() -> Strategy + Send>;
+
+// plays stand-in bots over tcp, bot a first, with the example bot as bot b when only one is given.
+// a stand-in process only hands its endpoint to the test, which answers for it with the strategy
+// its MakeStrategy builds
+fn run_stand_ins(strategies: Vec<MakeStrategy>, conf: &GameConfig, args: &[&str]) -> std::process::Output {
+    let config = config_file(conf);
+    let dir = tempfile::tempdir().unwrap();
+    let stand_ins: Vec<_> = strategies
+        .into_iter()
+        .zip(["a", "b"])
+        .map(|(make_strategy, side)| {
+            std::fs::create_dir(dir.path().join(side)).unwrap();
+            let (bot, endpoint) = (dir.path().join(side).join("scripted"), dir.path().join(side).join("endpoint"));
+            std::fs::write(&bot, format!("#!/bin/sh\necho \"$1\" > {}\nwhile true; do sleep 1; done\n", endpoint.display())).unwrap();
+            std::fs::set_permissions(&bot, std::fs::Permissions::from_mode(0o755)).unwrap();
+            (bot, endpoint, make_strategy)
+        })
+        .collect();
+
+    let example_bot = Path::new(env!("CARGO_BIN_EXE_bot")).to_path_buf();
+    let engine = Command::new(env!("CARGO_BIN_EXE_mm-engine"))
+        .arg(&stand_ins[0].0)
+        .arg(stand_ins.get(1).map_or(&example_bot, |stand_in| &stand_in.0))
+        .arg("--config")
+        .arg(config.path())
+        .args(["--transport", "tcp", "--print", "g"])
+        .args(args)
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    for (_, endpoint, make_strategy) in stand_ins {
+        let start = Instant::now();
+        let endpoint = loop {
+            match std::fs::read_to_string(&endpoint) {
+                Ok(endpoint) if endpoint.ends_with('\n') => break endpoint.trim().to_string(),
+                _ if start.elapsed() > Duration::from_secs(10) => panic!("the engine never started the bot"),
+                _ => std::thread::sleep(Duration::from_millis(10)),
+            }
+        };
+        std::thread::spawn(move || {
+            tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap().block_on(async {
+                let strategy = make_strategy();
+                let Ok(mut channel) = EngineConnection::connect(&endpoint).await else { return };
+                while channel.handle_msg(&strategy).await.is_ok() {}
+            })
+        });
+    }
+    engine.wait_with_output().unwrap()
+}
+
+// a stand-in as bot a against the example bot
+fn run_scripted(make_strategy: impl FnOnce() -> Strategy + Send + 'static, conf: &GameConfig, args: &[&str]) -> std::process::Output {
+    run_stand_ins(vec![Box::new(make_strategy)], conf, args)
+}
+
+fn idle_strategy() -> Strategy {
+    Strategy {
+        on_handshake: Box::new(|_| HandshakeResponse::CURRENT),
+        on_reset: Box::new(|_| Default::default()),
+        on_tick: Box::new(|_| Default::default()),
+    }
+}
+
+#[test]
+#[ignore = "spawns the engine and two bot processes, run with --ignored"]
+fn outdated_bot_is_refused() {
+    let played = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let flag = played.clone();
+    let outdated = move || Strategy {
+        on_handshake: Box::new(|_| HandshakeResponse { version: PROTOCOL_VERSION - 1, ..HandshakeResponse::CURRENT }),
+        on_tick: Box::new(move |_| {
+            flag.store(true, std::sync::atomic::Ordering::Relaxed);
+            Default::default()
+        }),
+        ..idle_strategy()
+    };
+    let conf = GameConfig { max_ticks: 100, endgame_ticks: 0, ..Default::default() };
+    let output = run_scripted(outdated, &conf, &[]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let refusal = format!(
+        "### FATAL ERROR: bot A:scripted failed handshake: bot speaks protocol version {}, engine speaks version {}",
+        PROTOCOL_VERSION - 1,
+        PROTOCOL_VERSION
+    );
+    assert!(stdout.lines().any(|line| line == refusal), "{stdout}");
+    assert!(!played.load(std::sync::atomic::Ordering::Relaxed));
+    assert_eq!(result_of(&stdout).winner, Some(Team::B));
+}
+
+#[test]
+#[ignore = "spawns the engine and two bot processes, run with --ignored"]
+fn slow_bot_reports_a_slow_response_time() {
+    let slow = || Strategy {
+        on_tick: Box::new(|_| {
+            std::thread::sleep(Duration::from_millis(5));
+            Default::default()
+        }),
+        ..idle_strategy()
+    };
+    let conf = GameConfig { max_ticks: 20, endgame_ticks: 0, ..Default::default() };
+    let output = run_scripted(slow, &conf, &["--tick-time-us", "1000"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let mean = |name: &str| {
+        let line = stdout.lines().find(|line| line.starts_with(&format!("# bot {name} response time"))).unwrap();
+        millis(line.split("mean ").nth(1).unwrap().split(' ').next().unwrap())
+    };
+    assert!(mean("A:scripted") >= 4.0, "{stdout}");
+    assert!(mean("B:bot") < 4.0, "{stdout}");
+}
+
+#[test]
+#[ignore = "spawns the engine and two bot processes, run with --ignored"]
+fn slowmo_stretches_the_match() {
+    // a bot that takes 5ms a tick, so each frame's time is steady enough to stretch
+    let slow = || Strategy {
+        on_tick: Box::new(|_| {
+            std::thread::sleep(Duration::from_millis(5));
+            Default::default()
+        }),
+        ..idle_strategy()
+    };
+    let conf = GameConfig { max_ticks: 100, endgame_ticks: 0, ..Default::default() };
+    let timed = |slowmo: &str| {
+        let start = Instant::now();
+        let output = run_scripted(slow, &conf, &["--tick-time-us", "1000", "--slowmo", slowmo]);
+        assert!(output.status.success());
+        start.elapsed().as_secs_f64()
+    };
+    let (normal, stretched) = (timed("1"), timed("2"));
+    let ratio = stretched / normal;
+    assert!((1.5..2.5).contains(&ratio), "normal {normal:.3}s, slowmo 2 {stretched:.3}s");
+}
+
+#[test]
+#[ignore = "spawns the engine and two bot processes, run with --ignored"]
+fn opponent_budget_is_shown_when_enabled() {
+    // bot a notes the budget it's shown for bot b, which spends 5ms, or 4 ticks past the delay, per tick
+    let seen = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let watcher = {
+        let seen = seen.clone();
+        move || Strategy {
+            on_tick: Box::new(move |state| {
+                seen.lock().unwrap().push(state.opponent_compute.option());
+                Default::default()
+            }),
+            ..idle_strategy()
+        }
+    };
+    let slow = || Strategy {
+        on_tick: Box::new(|_| {
+            std::thread::sleep(Duration::from_millis(5));
+            Default::default()
+        }),
+        ..idle_strategy()
+    };
+    let conf = GameConfig { max_ticks: 30, endgame_ticks: 0, expose_opponent_compute: true, ..Default::default() };
+    let args = ["--tick-time-us", "1000", "--delay-ticks", "1", "--compute-budget", "1000"];
+    let output = run_stand_ins(vec![Box::new(watcher), Box::new(slow)], &conf, &args);
+    assert!(output.status.success());
+
+    let seen: Vec<u32> = seen.lock().unwrap().iter().map(|ticks| ticks.expect("no opponent budget")).collect();
+    assert_eq!(seen.len(), 30);
+    assert!(seen.windows(2).all(|pair| pair[1] < pair[0]), "{seen:?}");
+    assert!(seen[0] <= 1000 && seen[29] <= 1000 - 29 * 4, "{seen:?}");
+}
+
+#[test]
+#[ignore = "spawns the engine and two bot processes, run with --ignored"]
+fn coasting_bot_repeats_its_last_actions() {
+    // runs everyone right, but misses the deadline for the tick starting at tick 5
+    let runner = || Strategy {
+        on_tick: Box::new(|state| {
+            if state.tick == 5 {
+                std::thread::sleep(Duration::from_millis(300));
+            }
+            std::array::from_fn(|_| PlayerAction { dir: Vec2::new(1.0, 0.0), ..Default::default() })
+        }),
+        ..idle_strategy()
+    };
+    // how far player 0 moved over the timed out tick
+    let moved = |timeout_policy: TimeoutPolicy| {
+        let conf = GameConfig { max_ticks: 10, endgame_ticks: 0, timeout_policy, ..Default::default() };
+        let output = run_scripted(runner, &conf, &["--tick-time-us", "1000", "--compute-budget", "50"]);
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let x = |tick: u64| {
+            stdout
+                .lines()
+                .filter_map(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+                .find(|state| state["tick"] == tick)
+                .map(|state| state["players"][0]["pos"]["x"].as_f64().unwrap())
+                .unwrap_or_else(|| panic!("no state for tick {tick}: {stdout}"))
+        };
+        x(6) - x(5)
+    };
+    let speed = GameConfig::default().player.speed as f64;
+    assert!((moved(TimeoutPolicy::Coast) - speed).abs() < 1e-3);
+    assert!(moved(TimeoutPolicy::Stop).abs() < 1e-3);
+}
+
+#[test]
+#[ignore = "spawns the engine and two bot processes, run with --ignored"]
+fn idle_bot_is_warned_once() {
+    let conf = GameConfig { max_ticks: 50, endgame_ticks: 0, ..Default::default() };
+    let output = run_scripted(idle_strategy, &conf, &["--idle-warn-ticks", "10"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let warnings: Vec<_> = stdout.lines().filter(|line| line.contains("warning: no actions")).collect();
+    assert_eq!(warnings, ["### [bot A:scripted] warning: no actions for 10 ticks, bot may be stuck or erroring"]);
+}
+
+// both sides' kickoff formation, in the bot's own coordinates
+fn spread() -> [Vec2; NUM_PLAYERS as usize] {
+    std::array::from_fn(|i| Vec2::new(300.0, 100.0 + 120.0 * i as f32))
+}
+
+// everyone chases the ball and the carrier shoots, so shot error draws from the rng
+fn chaser(state: &GameState) -> [PlayerAction; NUM_PLAYERS as usize] {
+    let goal = Vec2::new(GameConfig::default().field.width as f32, 300.0);
+    std::array::from_fn(|i| {
+        let player = &state.players[i];
+        let mut action = PlayerAction { dir: (state.ball.pos - player.pos).normalize_or_zero(), ..Default::default() };
+        if state.ball_owner() == Some(i as u32) {
+            action.shoot = StateOption::Some((goal - player.pos).normalize_or_zero());
+        }
+        action
+    })
+}
+
+fn chaser_strategy() -> Strategy {
+    Strategy {
+        on_handshake: Box::new(|_| HandshakeResponse::CURRENT),
+        on_reset: Box::new(|_| spread()),
+        on_tick: Box::new(chaser),
+    }
+}
+
+#[test]
+#[ignore = "spawns the engine and two bot processes, run with --ignored"]
+fn resets_name_their_cause() {
+    // a chases until it scores, then b until it equalizes, then both stand still
+    let causes = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let recorded = causes.clone();
+    let a = move || Strategy {
+        on_reset: Box::new(move |msg| {
+            recorded.lock().unwrap().push(msg.cause);
+            spread()
+        }),
+        on_tick: Box::new(|state| if state.score.a == 0 { chaser(state) } else { Default::default() }),
+        ..idle_strategy()
+    };
+    let b = || Strategy {
+        on_reset: Box::new(|_| spread()),
+        on_tick: Box::new(|state| if state.score.a < state.score.b { chaser(state) } else { Default::default() }),
+        ..idle_strategy()
+    };
+    let conf = GameConfig {
+        max_ticks: 1500,
+        endgame_ticks: 20,
+        ..Default::default()
+    };
+    let output = run_stand_ins(vec![Box::new(a), Box::new(b)], &conf, &["--seed", "9"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(result_of(&stdout).score, TeamPair::new(1, 1));
+
+    let causes = causes.lock().unwrap();
+    assert!(causes.contains(&ResetCause::Stagnation), "{causes:?}");
+    let played: Vec<_> = causes.iter().filter(|&&cause| cause != ResetCause::Stagnation).collect();
+    assert_eq!(played, [&ResetCause::NewGame, &ResetCause::Goal, &ResetCause::Goal, &ResetCause::Endgame]);
+}
+
+#[test]
+#[ignore = "spawns the engine and two bot processes, run with --ignored"]
+fn no_overtime_ends_a_tie_on_time() {
+    let conf = GameConfig { max_ticks: 100, endgame_ticks: 50, ..Default::default() };
+    // the last tick logged in a scoreless match between idle bots
+    let last_tick = |args: &[&str]| {
+        let output = run_stand_ins(vec![Box::new(idle_strategy), Box::new(idle_strategy)], &conf, args);
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let result = result_of(&stdout);
+        assert!(result.winner.is_none() && result.score == TeamPair::new(0, 0));
+        stdout
+            .lines()
+            .filter_map(|line| serde_json::from_str::<serde_json::Value>(line).ok()?["tick"].as_u64())
+            .max()
+            .unwrap()
+    };
+    assert_eq!(last_tick(&["--no-overtime"]), 100);
+    assert_eq!(last_tick(&[]), 150);
+}
+
+#[test]
+#[ignore = "spawns the engine and two bot processes, run with --ignored"]
+fn series_tally_matches_its_games() {
+    let config = config_file(&GameConfig { max_ticks: 600, endgame_ticks: 0, ..Default::default() });
+    // the first bot's head start follows it across sides, so the series isn't all ties
+    let stdout = run_engine(config.path(), &["--games", "4", "--seed", "3", "--start-score", "2:1"]);
+    let results: Vec<MatchResult> = stdout
+        .lines()
+        .filter_map(|line| line.strip_prefix("# result: "))
+        .map(|json| serde_json::from_str(json).unwrap())
+        .collect();
+    let tally: SeriesTally = stdout
+        .lines()
+        .find_map(|line| line.strip_prefix("# series: "))
+        .map(|json| serde_json::from_str(json).unwrap())
+        .expect("no series line");
+    assert_eq!(results.len(), 4);
+
+    // every other game the first bot plays as team b
+    let mut expected = SeriesTally { games: 4, ..Default::default() };
+    for (game, result) in results.iter().enumerate() {
+        let (first, second) = if game % 2 == 1 { (Team::B, Team::A) } else { (Team::A, Team::B) };
+        expected.goals.a += result.score[first];
+        expected.goals.b += result.score[second];
+        match result.winner {
+            Some(winner) if winner == first => expected.wins.a += 1,
+            Some(_) => expected.wins.b += 1,
+            None => expected.ties += 1,
+        }
+    }
+    assert!(expected.wins.a > 0);
+    assert_eq!(tally, expected);
+}
+
+#[test]
+#[ignore = "spawns the engine and two bot processes, run with --ignored"]
+fn frames_dir_gets_a_file_per_state() {
+    let config = config_file(&GameConfig { max_ticks: 20, endgame_ticks: 0, ..Default::default() });
+    let dir = tempfile::tempdir().unwrap();
+    let frames = dir.path().join("frames");
+    let stdout = run_engine(config.path(), &["--frames-dir", frames.to_str().unwrap()]);
+    let states: Vec<serde_json::Value> = stdout
+        .lines()
+        .filter_map(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+        .filter(|line| line.get("tick").is_some())
+        .collect();
+    assert!(!states.is_empty());
+
+    let mut names: Vec<_> = std::fs::read_dir(&frames)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    names.sort();
+    let expected: Vec<_> = (0..states.len())
+        .map(|i| format!("{i:05}.json"))
+        .chain(std::iter::once("config.json".to_string()))
+        .collect();
+    assert_eq!(names, expected);
+
+    let read = |name: &str| -> serde_json::Value {
+        serde_json::from_str(&std::fs::read_to_string(frames.join(name)).unwrap()).unwrap()
+    };
+    assert_eq!(read("config.json")["max_ticks"], 20);
+    assert_eq!(read("00000.json"), states[0]);
+    assert_eq!(read(&format!("{:05}.json", states.len() - 1)), states[states.len() - 1]);
+}
+
+#[test]
+#[ignore = "spawns the engine and two bot processes, run with --ignored"]
+fn simulate_tick_reproduces_an_engine_match() {
+    let conf = GameConfig { max_ticks: 400, endgame_ticks: 0, ..Default::default() };
+    let output = run_stand_ins(vec![Box::new(chaser_strategy), Box::new(chaser_strategy)], &conf, &["--seed", "9"]);
+    assert!(output.status.success());
+    let engine = result_of(&String::from_utf8(output.stdout).unwrap());
+
+    // the same match stepped directly, asking the strategy for each side's actions like the engine does
+    let mut formation = TeamPair::new(spread(), spread());
+    formation.b.iter_mut().for_each(|pos| mirror_pos(pos, &conf));
+    let mut state = GameState::new(&conf);
+    let mut rng = match_rng(9);
+    let mut checksum = Fnv1a::default();
+    let mut reset = true;
+    while !match_over(&state, &conf) {
+        if reset {
+            simulate_reset(&mut state, &conf, &formation);
+        }
+        let mut mirrored = state.clone();
+        mirrored.mirror(&conf);
+        let (mut a, mut b) = (chaser(&state), chaser(&mirrored));
+        a.iter_mut().chain(b.iter_mut()).for_each(|action| action.sanitize());
+        b.iter_mut().for_each(|action| action.mirror(&conf));
+        let actions = std::array::from_fn(|i| if i < a.len() { a[i].clone() } else { b[i - a.len()].clone() });
+        reset = simulate_tick(&mut state, &conf, actions, &mut rng).reset.is_some();
+        checksum.feed(&state.state_hash().to_le_bytes());
+    }
+    assert_eq!(format!("{:016x}", checksum.0), engine.checksum);
+    assert_eq!(state.score, engine.score);
+}
+
+#[test]
+#[ignore = "spawns the engine and two bot processes, run with --ignored"]
+fn fast_mode_plays_the_same_match_quickly() {
+    let config = config_file(&GameConfig { max_ticks: 2000, endgame_ticks: 0, ..Default::default() });
+
+    let start = Instant::now();
+    let fast = result_of(&run_engine(config.path(), &["--seed", "4", "--fast"]));
+    assert!(start.elapsed() < Duration::from_secs(20), "a fast match took {:?}", start.elapsed());
+    let normal = result_of(&run_engine(config.path(), &["--seed", "4"]));
+    assert_eq!(fast.checksum, normal.checksum);
+}
+
+#[test]
+#[ignore = "spawns the engine and two bot processes, run with --ignored"]
+fn tcp_transport_plays_the_same_match() {
+    let config = config_file(&GameConfig { max_ticks: 300, endgame_ticks: 0, ..Default::default() });
+    let states = |stdout: String| -> Vec<String> {
+        stdout.lines().filter(|line| line.starts_with('{') && line.contains("\"tick\"")).map(String::from).collect()
+    };
+
+    let shm = states(run_engine(config.path(), &["--seed", "3"]));
+    assert_eq!(shm.len(), 300);
+    assert_eq!(states(run_engine(config.path(), &["--seed", "3", "--transport", "tcp"])), shm);
+}
+
+#[test]
+#[ignore = "spawns the engine and two bot processes, run with --ignored"]
+fn series_plays_each_game_on_its_own_field() {
+    let mut narrow = GameConfig { max_ticks: 100, endgame_ticks: 0, ..Default::default() };
+    narrow.field.width = 800;
+    let mut wide = narrow.clone();
+    wide.field.width = 1600;
+    let (narrow_file, wide_file) = (config_file(&narrow), config_file(&wide));
+
+    let bot = env!("CARGO_BIN_EXE_bot");
+    let output = Command::new(env!("CARGO_BIN_EXE_mm-engine"))
+        .args([bot, bot, "--games", "2", "--seed", "1", "--print", "g"])
+        .arg("--config")
+        .arg(narrow_file.path())
+        .arg("--config")
+        .arg(wide_file.path())
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "engine failed: {}", String::from_utf8_lossy(&output.stderr));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    // split the gamelog at each game's config header
+    let mut games: Vec<(u32, f32)> = Vec::new();
+    for line in stdout.lines().filter(|line| line.starts_with('{')) {
+        let json: serde_json::Value = serde_json::from_str(line).unwrap();
+        if let Some(width) = json.pointer("/field/width") {
+            games.push((width.as_u64().unwrap() as u32, 0.0));
+        } else if let Some(players) = json["players"].as_array() {
+            let game = games.last_mut().expect("state before any config");
+            for player in players {
+                game.1 = game.1.max(player["pos"]["x"].as_f64().unwrap() as f32);
+            }
+        }
+    }
+    assert_eq!(games.len(), 2);
+    assert_eq!(games[0].0, narrow.field.width);
+    assert_eq!(games[1].0, wide.field.width);
+    assert!(games[0].1 <= narrow.field.width as f32, "a player left the narrow field");
+    // the wide game spreads out past the narrow field's edge
+    assert!(games[1].1 > narrow.field.width as f32);
+}
+
+#[test]
+#[ignore = "spawns the engine and two bot processes, run with --ignored"]
+fn summary_records_the_whole_match() {
+    let config = config_file(&GameConfig { max_ticks: 100, endgame_ticks: 0, ..Default::default() });
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("summary.json");
+
+    let stdout = run_engine(config.path(), &["--seed", "5", "--summary", path.to_str().unwrap()]);
+    let summary: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    let keys = [
+        "match_id", "seed", "score", "winner", "decided_by", "cancelled", "teams", "checksum",
+        "ticks", "elapsed_secs", "stats", "config",
+    ];
+    for key in keys {
+        assert!(summary.get(key).is_some(), "summary is missing {key}: {summary}");
+    }
+    assert_eq!(summary["seed"], 5);
+    assert_eq!(summary["ticks"], 100);
+    assert_eq!(summary["checksum"], result_of(&stdout).checksum);
+    assert_eq!(summary["config"]["max_ticks"], 100);
+}
+
+#[test]
+#[ignore = "spawns the engine and two bot processes, run with --ignored"]
+fn series_writes_a_summary_per_game() {
+    let config = config_file(&GameConfig { max_ticks: 50, endgame_ticks: 0, ..Default::default() });
+    let dir = tempfile::tempdir().unwrap();
+    let summary = dir.path().join("summary.json");
+
+    let bot = env!("CARGO_BIN_EXE_bot");
+    let status = Command::new(env!("CARGO_BIN_EXE_mm-engine"))
+        .args([bot, bot, "--games", "2", "--seed", "1"])
+        .arg("--config")
+        .arg(config.path())
+        .arg("--summary")
+        .arg(&summary)
+        .output()
+        .unwrap()
+        .status;
+    assert!(status.success());
+    assert!(!summary.exists());
+    for game in ["summary-1.json", "summary-2.json"] {
+        assert!(dir.path().join(game).exists(), "missing {game}");
+    }
+}
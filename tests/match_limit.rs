@@ -0,0 +1,43 @@
+//! The concurrency limit is process wide, so it gets a test binary of its own where no other
+//! test can run a match under it.
+
+use clap::Parser;
+use mm_engine::{ args::ArgConfig, engine::{ limit_concurrent_matches, run_match, CancelToken } };
+use std::{ os::unix::fs::PermissionsExt, time::Duration };
+use tokio::sync::mpsc;
+
+#[tokio::test]
+async fn match_limit_queues_the_second_match() {
+    assert!(limit_concurrent_matches(1));
+
+    // never answers, so each match holds its slot until cancelled
+    let dir = tempfile::tempdir().unwrap();
+    let bot = dir.path().join("silent-bot");
+    std::fs::write(&bot, "#!/bin/sh\nwhile true; do sleep 1; done\n").unwrap();
+    std::fs::set_permissions(&bot, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let bot = bot.to_str().unwrap();
+    let args = ArgConfig::try_parse_from(["mm-engine", bot, bot, "--handshake-timeout-secs", "60"]).unwrap();
+    let start = || {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let cancel = CancelToken::default();
+        let task = tokio::spawn({
+            let (args, cancel) = (args.clone(), cancel.clone());
+            async move { run_match(&args, &tx, &cancel).await }
+        });
+        (rx, cancel, task)
+    };
+
+    let (mut first_rx, first_cancel, first) = start();
+    let (mut second_rx, second_cancel, second) = start();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    // the first match has started and logged its config, the second is still waiting
+    assert!(first_rx.try_recv().is_ok());
+    assert!(second_rx.try_recv().is_err());
+
+    first_cancel.cancel();
+    assert!(first.await.unwrap().unwrap().cancelled);
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert!(second_rx.try_recv().is_ok());
+    second_cancel.cancel();
+    assert!(second.await.unwrap().unwrap().cancelled);
+}